@@ -55,7 +55,7 @@ impl expr::Visitor<String> for AstPrinter {
             }  
             TokenType::TRUE => format!("{}", "true"),
             TokenType::FALSE => format!("{}", "false"),
-            TokenType::STRING => format!("\"{}\"", value.lexeme),
+            TokenType::STRING => value.lexeme.clone(),
             _ => format!("no such literal"),
         }
     }
@@ -65,11 +65,11 @@ impl expr::Visitor<String> for AstPrinter {
         format!("(group {})", new_expr.accept(self))
     }
     
-    fn visit_variable(&mut self, name: &Token) -> String {
+    fn visit_variable(&mut self, name: &Token, id: usize) -> String {
         name.lexeme.to_string()
     }
     
-    fn visit_assign(&mut self, name: &Token, value: &Box<Expr>) -> String {
+    fn visit_assign(&mut self, name: &Token, value: &Box<Expr>, id: usize) -> String {
         format!("(= {} {})", name.lexeme, value.accept(self))
     }
     
@@ -88,21 +88,82 @@ impl expr::Visitor<String> for AstPrinter {
         call_expr
     }
     
-    // fn visit_this(&mut self, keyword: &Token) -> String {
-    //     "this".to_string()
-    // }
+    fn visit_this(&mut self, keyword: &Token, id: usize) -> String {
+        "this".to_string()
+    }
     
-    // fn visit_super(&mut self, keyword: &Token, method: &Token) -> String {
-    //     format!("(super {})", method.lexeme)
-    // }
+    fn visit_super(&mut self, keyword: &Token, method: &Token, id: usize) -> String {
+        format!("(super {})", method.lexeme)
+    }
     
-    // fn visit_set(&mut self, object: &Box<Expr>, name: &Token, value: &Box<Expr>) -> String {
-    //     format!("(set {} {} {})", object.accept(self), name.lexeme, value.accept(self))
-    // }
+    fn visit_set(&mut self, object: &Box<Expr>, name: &Token, value: &Box<Expr>) -> String {
+        format!("(set {} {} {})", object.accept(self), name.lexeme, value.accept(self))
+    }
     
-    // fn visit_get(&mut self, object: &Box<Expr>, name: &Token) -> String {
-    //     format!("(get {} {})", object.accept(self), name.lexeme)
-    // }
+    fn visit_get(&mut self, object: &Box<Expr>, name: &Token) -> String {
+        format!("(get {} {})", object.accept(self), name.lexeme)
+    }
+
+    fn visit_list(&mut self, bracket: &Token, elements: &Vec<Expr>) -> String {
+        let mut list_expr = String::from("(list");
+        for element in elements {
+            list_expr.push(' ');
+            list_expr.push_str(&element.accept(self));
+        }
+        list_expr.push(')');
+        list_expr
+    }
+
+    fn visit_map(&mut self, brace: &Token, entries: &Vec<(Expr, Expr)>) -> String {
+        let mut map_expr = String::from("(map");
+        for (key, value) in entries {
+            map_expr.push_str(&format!(" ({} {})", key.accept(self), value.accept(self)));
+        }
+        map_expr.push(')');
+        map_expr
+    }
+
+    fn visit_index(&mut self, object: &Box<Expr>, bracket: &Token, index: &Box<Expr>) -> String {
+        format!("(index {} {})", object.accept(self), index.accept(self))
+    }
+
+    fn visit_set_index(&mut self, object: &Box<Expr>, bracket: &Token, index: &Box<Expr>, value: &Box<Expr>) -> String {
+        format!("(set-index {} {} {})", object.accept(self), index.accept(self), value.accept(self))
+    }
+
+    fn visit_interpolation(&mut self, start: &Token, parts: &Vec<Expr>) -> String {
+        let mut interpolation_expr = String::from("(interpolate");
+        for part in parts {
+            interpolation_expr.push(' ');
+            // the text parts' lexemes are fragments like `"a ${`, so show what they hold
+            match part {
+                Expr::Literal { value } => interpolation_expr.push_str(&format!("{:?}", value.value())),
+                _ => interpolation_expr.push_str(&part.accept(self)),
+            }
+        }
+        interpolation_expr.push(')');
+        interpolation_expr
+    }
+
+    fn visit_update(&mut self, target: &Box<Expr>, op: &Token, value: &Box<Expr>, postfix: bool) -> String {
+        if postfix {
+            return format!("(post{} {})", op.lexeme, target.accept(self));
+        }
+        format!("({} {} {})", op.lexeme, target.accept(self), value.accept(self))
+    }
+
+    fn visit_lambda(&mut self, name: &Token, params: &Vec<Token>, body: &Vec<Stmt>) -> String {
+        let mut lambda_expr = String::from("(lambda (");
+        for param in params {
+            lambda_expr.push_str(&format!(" {}", param.lexeme));
+        }
+        lambda_expr.push_str(" ) ");
+        for stmt in body {
+            lambda_expr.push_str(&stmt.accept(self));
+        }
+        lambda_expr.push(')');
+        lambda_expr
+    }
 }
 
 impl stmt::Visitor<String> for AstPrinter {
@@ -115,8 +176,19 @@ impl stmt::Visitor<String> for AstPrinter {
         block_stmt
     }
     
-    fn visit_class(&mut self, name: &Token, methods: &Vec<Stmt>) -> String {
+    fn visit_break(&mut self, keyword: &Token) -> String {
+        String::from("(break)")
+    }
+    
+    fn visit_continue(&mut self, keyword: &Token) -> String {
+        String::from("(continue)")
+    }
+    
+    fn visit_class(&mut self, name: &Token, superclass: &Option<Expr>, methods: &Vec<Stmt>) -> String {
         let mut class_stmt = String::from(format!("(class {} ", name.lexeme));
+        if let Some(superclass) = superclass {
+            class_stmt.push_str(&format!("(< {}) ", superclass.accept(self)));
+        }
         for mut method in methods.clone() {
             class_stmt.push_str(&format!("{}", method.accept(self)));
         }
@@ -141,6 +213,10 @@ impl stmt::Visitor<String> for AstPrinter {
         function_stmt
     }
     
+    fn visit_import(&mut self, keyword: &Token, path: &Token, name: &Token) -> String {
+        format!("(import {} {})", path.lexeme, name.lexeme)
+    }
+    
     fn visit_if(&mut self, condition: &Expr, then_branch: &Stmt, else_branch: &Option<Box<Stmt>>) -> String {
         let mut if_stmt = String::from("(if ");
         if_stmt.push_str(&format!("{}", condition.accept(self)));
@@ -164,6 +240,24 @@ impl stmt::Visitor<String> for AstPrinter {
         format!("(return {})", value.clone().unwrap().accept(self))
     }
     
+    fn visit_throw(&mut self, keyword: &Token, value: &Expr) -> String {
+        format!("(throw {})", value.accept(self))
+    }
+    
+    fn visit_try(&mut self, body: &Vec<Stmt>, catch_name: &Option<Token>, catch_body: &Option<Vec<Stmt>>, finally_body: &Option<Vec<Stmt>>) -> String {
+        let mut try_stmt = String::from("(try ");
+        try_stmt.push_str(&self.visit_block(body));
+        if let Some(catch_body) = catch_body {
+            let name = catch_name.as_ref().map(|name| name.lexeme.clone()).unwrap_or_default();
+            try_stmt.push_str(&format!("(catch {} {})", name, self.visit_block(catch_body)));
+        }
+        if let Some(finally_body) = finally_body {
+            try_stmt.push_str(&format!("(finally {})", self.visit_block(finally_body)));
+        }
+        try_stmt.push_str(")");
+        try_stmt
+    }
+    
     fn visit_var(&mut self, name: &Token, initializer: &Option<Expr>) -> String {
         if initializer.is_none() {
             return format!("(var {})", name.lexeme);
@@ -172,10 +266,13 @@ impl stmt::Visitor<String> for AstPrinter {
         format!("(var {} {})", name.lexeme, initializer.clone().unwrap().accept(self))
     }
     
-    fn visit_while(&mut self, condition: &Expr, body: &Stmt) -> String {
+    fn visit_while(&mut self, condition: &Expr, body: &Stmt, increment: &Option<Expr>, per_iteration: bool) -> String {
         let mut while_stmt = String::from("(while ");
         while_stmt.push_str(&format!("{}", condition.accept(self)));
         while_stmt.push_str(&format!("{}", body.clone().accept(self)));
+        if let Some(increment) = increment {
+            while_stmt.push_str(&format!("(step {})", increment.accept(self)));
+        }
         while_stmt.push_str(")");
         while_stmt
     }    
@@ -1,4 +1,8 @@
-use crate::token::Token;
+use crate::ast::stmt::Stmt;
+use crate::token::{Span, Token};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
 
 #[derive(Debug, Clone)]
 pub enum Expr {
@@ -13,8 +17,10 @@ pub enum Expr {
         right: Box<Expr>,
     },
 
+    // nodes closed by a delimiter record their full extent, the rest derive it
     Grouping {
         expr: Box<Expr>,
+        span: Span,
     },
 
     Literal {
@@ -23,11 +29,13 @@ pub enum Expr {
     
     Variable {
         name: Token,
+        id: usize,
     },
     
     Assign {
         name: Token,
         value: Box<Expr>,
+        id: usize,
     },
     
     Call {
@@ -36,14 +44,16 @@ pub enum Expr {
         arguments: Vec<Expr>,
     },
     
-    // This {
-    //     keyword: Token,
-    // },
+    This {
+        keyword: Token,
+        id: usize,
+    },
     
-    // Super {
-    //     keyword: Token,
-    //     method: Token,
-    // },
+    Super {
+        keyword: Token,
+        method: Token,
+        id: usize,
+    },
     
     Logical {
         op: Token,
@@ -51,17 +61,68 @@ pub enum Expr {
         right: Box<Expr>,
     },
     
-    // Set {
-    //     object: Box<Expr>,
-    //     name: Token,
-    //     value: Box<Expr>,
-    // },
+    Set {
+        object: Box<Expr>,
+        name: Token,
+        value: Box<Expr>,
+    },
+    
+    Get {
+        object: Box<Expr>,
+        name: Token,
+    },
+    
+    List {
+        bracket: Token,
+        elements: Vec<Expr>,
+        span: Span,
+    },
+    
+    Map {
+        brace: Token,
+        entries: Vec<(Expr, Expr)>,
+        span: Span,
+    },
     
-    // Get {
-    //     object: Box<Expr>,
-    //     name: Token,
-    // },
+    Index {
+        object: Box<Expr>,
+        bracket: Token,
+        index: Box<Expr>,
+        span: Span,
+    },
     
+    SetIndex {
+        object: Box<Expr>,
+        bracket: Token,
+        index: Box<Expr>,
+        value: Box<Expr>,
+    },
+
+    // `target op= value`, `++target` or `target++`, reading and writing `target`
+    // (a variable, property or index) once. `op` is the arithmetic to apply, typed
+    // as the binary operator but spelled as written; a postfix update yields the old value.
+    Update {
+        target: Box<Expr>,
+        op: Token,
+        value: Box<Expr>,
+        postfix: bool,
+        span: Span,
+    },
+
+    // `"a ${b} c"`: string literals for the text, in order with the expressions
+    Interpolation {
+        start: Token,
+        parts: Vec<Expr>,
+        span: Span,
+    },
+
+    // `fn (a, b) { ... }` or `(a, b) => ...`; `name` is made up, at the start of the expression
+    Lambda {
+        name: Token,
+        params: Vec<Token>,
+        body: Vec<Stmt>,
+        span: Span,
+    },
 }
 
 pub trait Visitor<R> {
@@ -69,31 +130,73 @@ pub trait Visitor<R> {
     fn visit_binary(&mut self, op: &Token, left: &Box<Expr>, right: &Box<Expr>) -> R;
     fn visit_grouping(&mut self, expr: &Box<Expr>) -> R;
     fn visit_literal(&mut self, value: &Token) -> R;
-    fn visit_variable(&mut self, name: &Token) -> R;
-    fn visit_assign(&mut self, name: &Token, value: &Box<Expr>) -> R;
+    fn visit_variable(&mut self, name: &Token, id: usize) -> R;
+    fn visit_assign(&mut self, name: &Token, value: &Box<Expr>, id: usize) -> R;
     fn visit_call(&mut self, callee: &Box<Expr>, paren: &Token, arguments: &Vec<Expr>) -> R;
-    // fn visit_this(&self, keyword: &Token) -> R;
-    // fn visit_super(&self, keyword: &Token, method: &Token) -> R;
+    fn visit_this(&mut self, keyword: &Token, id: usize) -> R;
+    fn visit_super(&mut self, keyword: &Token, method: &Token, id: usize) -> R;
     fn visit_logical(&mut self, op: &Token, left: &Box<Expr>, right: &Box<Expr>) -> R;
-    // fn visit_set(&self, object: &Box<Expr>, name: &Token, value: &Box<Expr>) -> R;
-    // fn visit_get(&self, object: &Box<Expr>, name: &Token) -> R;
+    fn visit_set(&mut self, object: &Box<Expr>, name: &Token, value: &Box<Expr>) -> R;
+    fn visit_get(&mut self, object: &Box<Expr>, name: &Token) -> R;
+    fn visit_list(&mut self, bracket: &Token, elements: &Vec<Expr>) -> R;
+    fn visit_map(&mut self, brace: &Token, entries: &Vec<(Expr, Expr)>) -> R;
+    fn visit_index(&mut self, object: &Box<Expr>, bracket: &Token, index: &Box<Expr>) -> R;
+    fn visit_set_index(&mut self, object: &Box<Expr>, bracket: &Token, index: &Box<Expr>, value: &Box<Expr>) -> R;
+    fn visit_lambda(&mut self, name: &Token, params: &Vec<Token>, body: &Vec<Stmt>) -> R;
+    fn visit_interpolation(&mut self, start: &Token, parts: &Vec<Expr>) -> R;
+    fn visit_update(&mut self, target: &Box<Expr>, op: &Token, value: &Box<Expr>, postfix: bool) -> R;
 }
 
 impl Expr {
+    // identifies a variable reference so the resolver can record its scope distance
+    pub fn next_id() -> usize {
+        NEXT_ID.fetch_add(1, Ordering::Relaxed)
+    }
+
+    // the source text the expression was parsed from
+    pub fn span(&self) -> Span {
+        match self {
+            Expr::Unary { op, value } => op.span.to(&value.span()),
+            Expr::Binary { left, right, .. } | Expr::Logical { left, right, .. } => left.span().to(&right.span()),
+            Expr::Grouping { span, .. }
+            | Expr::List { span, .. }
+            | Expr::Map { span, .. }
+            | Expr::Index { span, .. }
+            | Expr::Lambda { span, .. }
+            | Expr::Interpolation { span, .. }
+            | Expr::Update { span, .. } => *span,
+            Expr::Literal { value } => value.span,
+            Expr::Variable { name, .. } => name.span,
+            Expr::Assign { name, value, .. } => name.span.to(&value.span()),
+            Expr::Call { callee, paren, .. } => callee.span().to(&paren.span),
+            Expr::This { keyword, .. } => keyword.span,
+            Expr::Super { keyword, method, .. } => keyword.span.to(&method.span),
+            Expr::Set { object, value, .. } | Expr::SetIndex { object, value, .. } => object.span().to(&value.span()),
+            Expr::Get { object, name } => object.span().to(&name.span),
+        }
+    }
+
     pub fn accept<R>(&self, visitor: &mut impl Visitor<R>) -> R {
         match self {
             Expr::Unary { op, value } => visitor.visit_unary(op, value),
             Expr::Binary { op, left, right } => visitor.visit_binary(op, left, right),
             Expr::Literal { value } => visitor.visit_literal(value),
-            Expr::Grouping { expr } => visitor.visit_grouping(expr),
-            Expr::Variable { name } => visitor.visit_variable(name),
-            Expr::Assign { name, value } => visitor.visit_assign(name, value),
+            Expr::Grouping { expr, .. } => visitor.visit_grouping(expr),
+            Expr::Variable { name, id } => visitor.visit_variable(name, *id),
+            Expr::Assign { name, value, id } => visitor.visit_assign(name, value, *id),
             Expr::Call { callee, paren, arguments } => visitor.visit_call(callee, paren, arguments),
-            // Expr::This { keyword } => visitor.visit_this(keyword),
-            // Expr::Super { keyword, method } => visitor.visit_super(keyword, method),
+            Expr::This { keyword, id } => visitor.visit_this(keyword, *id),
+            Expr::Super { keyword, method, id } => visitor.visit_super(keyword, method, *id),
             Expr::Logical { op, left, right } => visitor.visit_logical(op, left, right),
-            // Expr::Set { object, name, value } => visitor.visit_set(object, name, value),
-            // Expr::Get { object, name } => visitor.visit_get(object, name),
+            Expr::Set { object, name, value } => visitor.visit_set(object, name, value),
+            Expr::Get { object, name } => visitor.visit_get(object, name),
+            Expr::List { bracket, elements, .. } => visitor.visit_list(bracket, elements),
+            Expr::Map { brace, entries, .. } => visitor.visit_map(brace, entries),
+            Expr::Index { object, bracket, index, .. } => visitor.visit_index(object, bracket, index),
+            Expr::SetIndex { object, bracket, index, value } => visitor.visit_set_index(object, bracket, index, value),
+            Expr::Lambda { name, params, body, .. } => visitor.visit_lambda(name, params, body),
+            Expr::Interpolation { start, parts, .. } => visitor.visit_interpolation(start, parts),
+            Expr::Update { target, op, value, postfix, .. } => visitor.visit_update(target, op, value, *postfix),
         }
     }
 }
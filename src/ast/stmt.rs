@@ -1,77 +1,155 @@
-use crate::token::Token;
+use crate::token::{Span, Token};
 use super::expr::Expr;
 
+// every statement records the source it spans, from its leading keyword to its `;` or `}`
 #[derive(Debug, Clone)]
 pub enum Stmt {
     Block {
         stmts: Vec<Stmt>,
+        span: Span,
+    },
+    
+    Break {
+        keyword: Token,
+        span: Span,
+    },
+    
+    Continue {
+        keyword: Token,
+        span: Span,
     },
     
     Expression {
         expr: Expr,
+        span: Span,
     },
     
     Class {
         name: Token,
+        superclass: Option<Expr>,
         methods: Vec<Stmt>,
+        span: Span,
     },
     
     Function {
         name: Token,
         params: Vec<Token>,
         body: Vec<Stmt>,
+        span: Span,
+    },
+    
+    // `name` is the `as` binding, or the file stem when there is none
+    Import {
+        keyword: Token,
+        path: Token,
+        name: Token,
+        span: Span,
     },
     
     If {
         condition: Expr,
         then_branch: Box<Stmt>,
         else_branch: Option<Box<Stmt>>,
+        span: Span,
     },
     
     Print {
         expr: Expr,
+        span: Span,
     },
     
     Return {
         keyword: Token,
         value: Option<Expr>,
+        span: Span,
+    },
+    
+    Throw {
+        keyword: Token,
+        value: Expr,
+        span: Span,
+    },
+    
+    // at least one of `catch_body` and `finally_body` is present
+    Try {
+        body: Vec<Stmt>,
+        catch_name: Option<Token>,
+        catch_body: Option<Vec<Stmt>>,
+        finally_body: Option<Vec<Stmt>>,
+        span: Span,
     },
     
     Var {
         name: Token,
         initializer: Option<Expr>,
+        span: Span,
     },
     
+    // `increment` is only set for desugared `for` loops, and runs after `continue` too.
+    // `per_iteration` marks a `for` that declares its variable: each iteration gets its
+    // own copy, made before the increment, so closures keep the value they saw.
     While {
         condition: Expr,
         body: Box<Stmt>,
+        increment: Option<Expr>,
+        per_iteration: bool,
+        span: Span,
     },
 }
 
 pub trait Visitor<R> {
     fn visit_block(&mut self, stmts: &Vec<Stmt>) -> R;
+    fn visit_break(&mut self, keyword: &Token) -> R;
+    fn visit_continue(&mut self, keyword: &Token) -> R;
     fn visit_expression(&mut self, expr: &Expr) -> R;
-    fn visit_class(&mut self, name: &Token, methods: &Vec<Stmt>) -> R;
+    fn visit_class(&mut self, name: &Token, superclass: &Option<Expr>, methods: &Vec<Stmt>) -> R;
     fn visit_function(&mut self, name: &Token, params: &Vec<Token>, body: &Vec<Stmt>) -> R;
+    fn visit_import(&mut self, keyword: &Token, path: &Token, name: &Token) -> R;
     fn visit_if(&mut self, condition: &Expr, then_branch: &Stmt, else_branch: &Option<Box<Stmt>>) -> R;
     fn visit_print(&mut self, expr: &Expr) -> R;
     fn visit_return(&mut self, keyword: &Token, value: &Option<Expr>) -> R;
+    fn visit_throw(&mut self, keyword: &Token, value: &Expr) -> R;
+    fn visit_try(&mut self, body: &Vec<Stmt>, catch_name: &Option<Token>, catch_body: &Option<Vec<Stmt>>, finally_body: &Option<Vec<Stmt>>) -> R;
     fn visit_var(&mut self, name: &Token, initializer: &Option<Expr>) -> R;
-    fn visit_while(&mut self, condition: &Expr, body: &Stmt) -> R;
+    fn visit_while(&mut self, condition: &Expr, body: &Stmt, increment: &Option<Expr>, per_iteration: bool) -> R;
 }
 
 impl Stmt {
+    pub fn span(&self) -> Span {
+        match self {
+            Stmt::Block { span, .. }
+            | Stmt::Break { span, .. }
+            | Stmt::Continue { span, .. }
+            | Stmt::Expression { span, .. }
+            | Stmt::Class { span, .. }
+            | Stmt::Function { span, .. }
+            | Stmt::Import { span, .. }
+            | Stmt::If { span, .. }
+            | Stmt::Print { span, .. }
+            | Stmt::Return { span, .. }
+            | Stmt::Throw { span, .. }
+            | Stmt::Try { span, .. }
+            | Stmt::Var { span, .. }
+            | Stmt::While { span, .. } => *span,
+        }
+    }
+
     pub fn accept<R>(&self, visitor: &mut impl Visitor<R>) -> R {
         match self {
-            Stmt::Block { stmts } => visitor.visit_block(stmts),
-            Stmt::Expression { expr } => visitor.visit_expression(expr),
-            Stmt::Class { name, methods } => visitor.visit_class(name, methods),
-            Stmt::Function { name, params, body } => visitor.visit_function(name, params, body),
-            Stmt::If { condition, then_branch, else_branch } => visitor.visit_if(condition, then_branch, else_branch),
-            Stmt::Print { expr } => visitor.visit_print(expr),
-            Stmt::Return { keyword, value } => visitor.visit_return(keyword, value),
-            Stmt::Var { name, initializer } => visitor.visit_var(name, initializer),
-            Stmt::While { condition, body } => visitor.visit_while(condition, body),
+            Stmt::Block { stmts, .. } => visitor.visit_block(stmts),
+            Stmt::Break { keyword, .. } => visitor.visit_break(keyword),
+            Stmt::Continue { keyword, .. } => visitor.visit_continue(keyword),
+            Stmt::Expression { expr, .. } => visitor.visit_expression(expr),
+            Stmt::Class { name, superclass, methods, .. } => visitor.visit_class(name, superclass, methods),
+            Stmt::Function { name, params, body, .. } => visitor.visit_function(name, params, body),
+            Stmt::Import { keyword, path, name, .. } => visitor.visit_import(keyword, path, name),
+            Stmt::If { condition, then_branch, else_branch, .. } => visitor.visit_if(condition, then_branch, else_branch),
+            Stmt::Print { expr, .. } => visitor.visit_print(expr),
+            Stmt::Return { keyword, value, .. } => visitor.visit_return(keyword, value),
+            Stmt::Throw { keyword, value, .. } => visitor.visit_throw(keyword, value),
+            Stmt::Try { body, catch_name, catch_body, finally_body, .. } => visitor.visit_try(body, catch_name, catch_body, finally_body),
+            Stmt::Var { name, initializer, .. } => visitor.visit_var(name, initializer),
+            Stmt::While { condition, body, increment, per_iteration, .. } => visitor.visit_while(condition, body, increment, *per_iteration),
         }
     }
 }
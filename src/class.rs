@@ -0,0 +1,133 @@
+use crate::error::RuntimeError;
+use crate::function::Function;
+use crate::object::Object;
+use crate::token::Token;
+use crate::vm::Closure;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt;
+use std::rc::Rc;
+
+// A method as each backend represents it: the tree-walker keeps the declaration,
+// the VM a compiled closure.
+#[derive(Debug, Clone)]
+pub enum Method {
+    Function(Function),
+    Closure(Rc<Closure>),
+}
+
+impl Method {
+    pub fn name(&self) -> &Token {
+        match self {
+            Method::Function(func) => func.name(),
+            Method::Closure(closure) => &closure.function.name,
+        }
+    }
+
+    pub fn arity(&self) -> usize {
+        match self {
+            Method::Function(func) => func.arity(),
+            Method::Closure(closure) => closure.function.arity,
+        }
+    }
+
+    pub fn bind(&self, instance: Object) -> Object {
+        match self {
+            Method::Function(func) => Object::Callable {
+                func: func.bind(instance),
+            },
+            Method::Closure(closure) => Object::BoundMethod {
+                receiver: Box::new(instance),
+                method: Rc::clone(closure),
+            },
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Class {
+    pub name: String,
+    superclass: Option<Rc<Class>>,
+    methods: HashMap<String, Method>,
+}
+
+impl Class {
+    pub fn new(
+        name: String,
+        superclass: Option<Rc<Class>>,
+        methods: HashMap<String, Method>,
+    ) -> Class {
+        Class {
+            name,
+            superclass,
+            methods,
+        }
+    }
+
+    // walks up the superclass chain until some class defines the method
+    pub fn find_method(&self, name: &str) -> Option<Method> {
+        match self.methods.get(name) {
+            Some(method) => Some(method.clone()),
+            None => match &self.superclass {
+                Some(superclass) => superclass.find_method(name),
+                None => None,
+            },
+        }
+    }
+
+    pub fn arity(&self) -> usize {
+        match self.find_method("init") {
+            Some(init) => init.arity(),
+            None => 0,
+        }
+    }
+}
+
+impl fmt::Display for Class {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "<class {}>", self.name)
+    }
+}
+
+#[derive(Debug)]
+pub struct Instance {
+    pub class: Rc<Class>,
+    fields: HashMap<String, Object>,
+    // for the `Error` a catch block binds, the error it describes
+    pub error: Option<RuntimeError>,
+}
+
+impl Instance {
+    pub fn new(class: Rc<Class>) -> Instance {
+        Instance {
+            class,
+            fields: HashMap::new(),
+            error: None,
+        }
+    }
+
+    // fields shadow methods; methods are bound to the instance they were looked up on
+    pub fn get(instance: &Rc<RefCell<Instance>>, name: &Token) -> Result<Object, RuntimeError> {
+        if let Some(value) = instance.borrow().fields.get(&name.lexeme) {
+            return Ok(value.clone());
+        }
+
+        let method = instance.borrow().class.find_method(&name.lexeme);
+        match method {
+            Some(method) => Ok(method.bind(Object::Instance {
+                instance: Rc::clone(instance),
+            })),
+            None => Err(RuntimeError::UndefinedProperty((*name).clone())),
+        }
+    }
+
+    pub fn set(&mut self, name: &Token, value: Object) {
+        self.fields.insert(name.lexeme.clone(), value);
+    }
+}
+
+impl fmt::Display for Instance {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "<{} instance>", self.class.name)
+    }
+}
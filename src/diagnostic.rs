@@ -0,0 +1,223 @@
+use crate::error::{ParserError, RuntimeError};
+use crate::token::Span;
+use std::env;
+use std::fmt::Write;
+use std::io::{self, IsTerminal};
+
+const RED: &str = "\x1b[1;31m";
+const BLUE: &str = "\x1b[1;34m";
+const BOLD: &str = "\x1b[1m";
+const RESET: &str = "\x1b[0m";
+
+// longer tracebacks keep this many entries at each end and summarise the middle
+const TRACEBACK_EDGE: usize = 10;
+
+#[derive(Debug, Clone)]
+pub struct SourceFile {
+    pub name: String,
+    pub text: String,
+}
+
+// Every piece of source loaded during a run, indexed by `Span::file`.
+#[derive(Debug, Clone, Default)]
+pub struct SourceMap {
+    files: Vec<SourceFile>,
+}
+
+impl SourceMap {
+    pub fn new() -> SourceMap {
+        SourceMap::default()
+    }
+
+    pub fn add(&mut self, name: &str, text: &str) -> usize {
+        self.files.push(SourceFile {
+            name: name.to_string(),
+            text: text.to_string(),
+        });
+        self.files.len() - 1
+    }
+
+    pub fn get(&self, file: usize) -> Option<&SourceFile> {
+        self.files.get(file)
+    }
+}
+
+// A secondary location, e.g. the declaration an arity mismatch is checked against.
+#[derive(Debug, Clone)]
+pub struct Note {
+    pub span: Span,
+    pub message: String,
+}
+
+// An error ready to be shown to the user: the headline message, the source it points
+// at, and any notes. Rendered like
+//
+//     [line 3] Error: Unexpected character: $
+//      --> main.salmon:3:5
+//       |
+//     3 | var $ = 1;
+//       |     ^
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    pub message: String,
+    pub span: Option<Span>,
+    pub notes: Vec<Note>,
+    // for runtime errors: where each active function was executing, outermost first,
+    // and how many tail calls the call made there went through before the next entry
+    pub traceback: Vec<(Span, String, usize)>,
+}
+
+impl Diagnostic {
+    pub fn new(message: String) -> Diagnostic {
+        Diagnostic {
+            message,
+            span: None,
+            notes: Vec::new(),
+            traceback: Vec::new(),
+        }
+    }
+
+    pub fn with_span(mut self, span: Span) -> Diagnostic {
+        self.span = Some(span);
+        self
+    }
+
+    pub fn with_note(mut self, span: Span, message: String) -> Diagnostic {
+        self.notes.push(Note { span, message });
+        self
+    }
+
+    pub fn render(&self, sources: &SourceMap, colour: bool) -> String {
+        let paint = |code: &str, text: &str| {
+            if colour {
+                format!("{}{}{}", code, text, RESET)
+            } else {
+                text.to_string()
+            }
+        };
+
+        let mut out = String::new();
+        writeln!(out, "{}", paint(RED, &self.message)).unwrap();
+
+        let snippets: Vec<(Span, char, Option<&str>, &str)> = self
+            .span
+            .iter()
+            .map(|span| (*span, '^', None, RED))
+            .chain(self.notes.iter().map(|note| (note.span, '-', Some(note.message.as_str()), BLUE)))
+            .filter(|(span, ..)| span.column > 0 && sources.get(span.file).is_some())
+            .collect();
+        let width = snippets
+            .iter()
+            .map(|(span, ..)| span.line.to_string().len())
+            .max()
+            .unwrap_or(0);
+
+        for (i, (span, marker, label, code)) in snippets.iter().enumerate() {
+            let file = sources.get(span.file).unwrap();
+            let gutter = paint(BLUE, &format!("{:width$} |", ""));
+            // a note from another file gets its own location line
+            if i == 0 || snippets[i - 1].0.file != span.file {
+                let arrow = format!("{:width$}--> ", "");
+                writeln!(out, "{}{}:{}:{}", paint(BLUE, &arrow), file.name, span.line, span.column).unwrap();
+            }
+            let text = match file.text.lines().nth(span.line - 1) {
+                Some(text) => text,
+                None => continue,
+            };
+            writeln!(out, "{}", gutter).unwrap();
+            writeln!(out, "{} {}", paint(BLUE, &format!("{:>width$} |", span.line)), text).unwrap();
+
+            let underline = marker.to_string().repeat(underline_width(span, &file.text, text));
+            // keep tabs so the underline lines up with the text above it
+            let pad: String = text
+                .chars()
+                .take(span.column - 1)
+                .map(|c| if c == '\t' { '\t' } else { ' ' })
+                .collect();
+            match label {
+                Some(label) => writeln!(out, "{} {}{} {}", gutter, pad, paint(code, &underline), paint(BOLD, label)).unwrap(),
+                None => writeln!(out, "{} {}{}", gutter, pad, paint(code, &underline)).unwrap(),
+            }
+        }
+
+        if !self.traceback.is_empty() {
+            writeln!(out, "{}", paint(BOLD, "Traceback (most recent call last):")).unwrap();
+            let omitted = self.traceback.len().saturating_sub(2 * TRACEBACK_EDGE);
+            for (i, (span, function, elided)) in self.traceback.iter().enumerate() {
+                if omitted > 0 && i >= TRACEBACK_EDGE && i < TRACEBACK_EDGE + omitted {
+                    if i == TRACEBACK_EDGE {
+                        writeln!(out, "  ... {} more calls ...", omitted).unwrap();
+                    }
+                    continue;
+                }
+                let location = match sources.get(span.file) {
+                    Some(file) if span.column > 0 => format!("{}:{}:{}", file.name, span.line, span.column),
+                    _ => format!("line {}", span.line),
+                };
+                writeln!(out, "  {} in {}", location, function).unwrap();
+                if *elided > 0 {
+                    let noun = if *elided == 1 { "call" } else { "calls" };
+                    writeln!(out, "  ... {} tail {} elided ...", elided, noun).unwrap();
+                }
+            }
+        }
+        out
+    }
+
+    // writes to stderr, in colour when that is a terminal and `NO_COLOR` is unset
+    pub fn emit(&self, sources: &SourceMap) {
+        eprint!("{}", self.render(sources, use_colour()));
+    }
+}
+
+impl From<&ParserError> for Diagnostic {
+    fn from(error: &ParserError) -> Diagnostic {
+        let diagnostic = Diagnostic::new(error.to_string());
+        match error.span() {
+            Some(span) => diagnostic.with_span(span),
+            None => diagnostic,
+        }
+    }
+}
+
+impl From<&RuntimeError> for Diagnostic {
+    fn from(error: &RuntimeError) -> Diagnostic {
+        let mut diagnostic = Diagnostic::new(error.to_string());
+        if let Some(token) = error.token() {
+            diagnostic = diagnostic.with_span(token.span);
+        }
+        if let RuntimeError::ArityMismatch(_, expected, _, Some(declaration)) = error.root() {
+            let noun = if *expected == 1 { "parameter" } else { "parameters" };
+            diagnostic = diagnostic.with_note(
+                declaration.span,
+                format!("{} declared here with {} {}", declaration.lexeme, expected, noun),
+            );
+        }
+
+        // each call site sits in the function called one frame further out
+        let frames: Vec<_> = error.frames().iter().rev().collect();
+        if !frames.is_empty() {
+            let mut callers = vec!["<script>".to_string()];
+            callers.extend(frames.iter().map(|frame| frame.function.clone()));
+            for (frame, caller) in frames.iter().zip(&callers) {
+                diagnostic.traceback.push((frame.call_site.span, caller.clone(), frame.elided));
+            }
+            if let Some(token) = error.token() {
+                diagnostic.traceback.push((token.span, callers.last().unwrap().clone(), 0));
+            }
+        }
+        diagnostic
+    }
+}
+
+fn use_colour() -> bool {
+    io::stderr().is_terminal() && env::var_os("NO_COLOR").is_none()
+}
+
+// how many characters of `line` the span covers; spans running past the line stop at its end
+fn underline_width(span: &Span, source: &str, line: &str) -> usize {
+    let covered = source.get(span.start..span.end).unwrap_or("");
+    let covered = covered.split('\n').next().unwrap_or("");
+    let remaining = line.chars().count().saturating_sub(span.column - 1);
+    covered.chars().count().min(remaining).max(1)
+}
@@ -1,30 +1,45 @@
+use std::cell::RefCell;
 use std::fmt;
+use std::rc::Rc;
 
-use crate::{object::Object, token::Token};
-
-pub mod error {
-    pub fn report_error(loc: usize, message: &str) {
-        eprintln!("[line {}] Error: {}", loc, message);
-    }
-}
+use crate::{class::{Class, Instance}, interpreter::Frame, object::Object, token::{Span, Token, TokenType}};
 
 #[derive(Debug, Clone)]
 pub enum ParserError {
-    UnmatchedParens(usize, String),
-    UnexpectedToken(usize, String),
-    InvalidExpression(usize, String),
+    UnmatchedParens(Span, String),
+    UnexpectedToken(Span, String),
+    InvalidExpression(Span, String),
     UnexpectedEndOfFile,
-    FunctionError(usize, String),
+    FunctionError(Span, String),
+    ResolutionError(Span, String),
+    // where the parser gave up, and the error limit that made it
+    TooManyErrors(Span, usize),
+}
+
+impl ParserError {
+    pub fn span(&self) -> Option<Span> {
+        match self {
+            ParserError::UnmatchedParens(span, _)
+            | ParserError::UnexpectedToken(span, _)
+            | ParserError::InvalidExpression(span, _)
+            | ParserError::FunctionError(span, _)
+            | ParserError::ResolutionError(span, _)
+            | ParserError::TooManyErrors(span, _) => Some(*span),
+            ParserError::UnexpectedEndOfFile => None,
+        }
+    }
 }
 
 impl fmt::Display for ParserError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
-            ParserError::UnmatchedParens(line, loc) => write!(f, "[line {}] ParserError: Unmatched Parentheses at token: {}", line, loc),
-            ParserError::UnexpectedToken(line, msg) => write!(f, "[line {}] ParserError: Unexpected Token; {}", line, msg),
-            ParserError::InvalidExpression(line, loc) => write!(f, "[line {}] ParserError: Invalid Expression at token: {}", line, loc),
+            ParserError::UnmatchedParens(span, loc) => write!(f, "[line {}] ParserError: Unmatched Parentheses at token: {}", span.line, loc),
+            ParserError::UnexpectedToken(span, msg) => write!(f, "[line {}] ParserError: Unexpected Token; {}", span.line, msg),
+            ParserError::InvalidExpression(span, loc) => write!(f, "[line {}] ParserError: Invalid Expression at token: {}", span.line, loc),
             ParserError::UnexpectedEndOfFile => write!(f, "ParserError: Unexpected End of File"),
-            ParserError::FunctionError(line, loc) => write!(f, "[line {}] ParserError: Function Error at {}", line, loc),
+            ParserError::FunctionError(span, loc) => write!(f, "[line {}] ParserError: Function Error at {}", span.line, loc),
+            ParserError::ResolutionError(span, msg) => write!(f, "[line {}] ResolutionError: {}", span.line, msg),
+            ParserError::TooManyErrors(span, max) => write!(f, "[line {}] ParserError: Too many errors, stopped after {}", span.line, max),
         }
     }
 }
@@ -39,7 +54,141 @@ pub enum RuntimeError {
     InvalidLiteral(Token, String),
     InvalidLogicalOperation(Token, String),
     InvalidFunctionCall(Token, String),
-    Return(Object),
+    // expected and given argument counts, and the declaration being called when it has one
+    ArityMismatch(Token, usize, usize, Option<Token>),
+    UndefinedProperty(Token),
+    InvalidPropertyAccess(Token, String),
+    InvalidSuperclass(Token, String),
+    AssertionFailed(Token, String),
+    IndexOutOfRange(Token, String),
+    KeyNotFound(Token, String),
+    Thrown(Token, Object),
+    ImportError(Token, String),
+    StackOverflow(Token),
+    // an error that unwound out of one or more calls, with those frames innermost first
+    Traced(Box<RuntimeError>, Vec<Frame>),
+}
+
+impl RuntimeError {
+    // records that the error is leaving `frame` on its way up the call stack
+    pub fn with_frame(self, frame: Frame) -> RuntimeError {
+        match self {
+            RuntimeError::Traced(error, mut frames) => {
+                frames.push(frame);
+                RuntimeError::Traced(error, frames)
+            }
+            error => RuntimeError::Traced(Box::new(error), vec![frame]),
+        }
+    }
+
+    // Wraps the error in the `Error` instance a catch block binds, exposing
+    // `message`, `line`, `kind` and, for `throw`, the original `value`.
+    pub fn to_object(&self, error_class: &Rc<Class>) -> Object {
+        let mut instance = Instance::new(Rc::clone(error_class));
+        let line = self.token().map(|token| token.line).unwrap_or(0);
+        let value = match self.root() {
+            RuntimeError::Thrown(_, value) => value.clone(),
+            _ => Object::Null,
+        };
+        let field = |name: &str| Token::new(TokenType::IDENTIFIER, name.to_string(), line);
+
+        instance.set(&field("message"), Object::String { value: self.message() });
+        instance.set(&field("line"), Object::Number { value: line as f64 });
+        instance.set(&field("kind"), Object::String { value: self.kind().to_string() });
+        instance.set(&field("value"), value);
+        instance.error = Some(self.clone());
+        Object::Instance {
+            instance: Rc::new(RefCell::new(instance)),
+        }
+    }
+
+    // What `throw value` raises. Throwing a caught `Error` again re-raises the
+    // error it came from, keeping its kind, message, line and trace.
+    pub fn thrown(keyword: &Token, value: Object) -> RuntimeError {
+        if let Object::Instance { instance } = &value {
+            if let Some(error) = &instance.borrow().error {
+                return error.clone();
+            }
+        }
+        RuntimeError::Thrown((*keyword).clone(), value)
+    }
+
+    // the error as originally raised, without its trace
+    pub fn root(&self) -> &RuntimeError {
+        match self {
+            RuntimeError::Traced(error, _) => error,
+            error => error,
+        }
+    }
+
+    pub fn frames(&self) -> &[Frame] {
+        match self {
+            RuntimeError::Traced(_, frames) => frames,
+            _ => &[],
+        }
+    }
+
+    // the token the error is reported at
+    pub fn token(&self) -> Option<&Token> {
+        match self {
+            RuntimeError::InvalidBinaryOperation(token, _)
+            | RuntimeError::InvalidUnaryOperation(token, _)
+            | RuntimeError::InvalidOperandType(token, _)
+            | RuntimeError::UndefinedVariable(token)
+            | RuntimeError::InvalidLiteral(token, _)
+            | RuntimeError::InvalidLogicalOperation(token, _)
+            | RuntimeError::InvalidFunctionCall(token, _)
+            | RuntimeError::ArityMismatch(token, ..)
+            | RuntimeError::UndefinedProperty(token)
+            | RuntimeError::InvalidPropertyAccess(token, _)
+            | RuntimeError::InvalidSuperclass(token, _)
+            | RuntimeError::AssertionFailed(token, _)
+            | RuntimeError::IndexOutOfRange(token, _)
+            | RuntimeError::KeyNotFound(token, _)
+            | RuntimeError::Thrown(token, _)
+            | RuntimeError::ImportError(token, _)
+            | RuntimeError::StackOverflow(token) => Some(token),
+            RuntimeError::Traced(error, _) => error.token(),
+        }
+    }
+
+    pub fn kind(&self) -> &'static str {
+        match self {
+            RuntimeError::InvalidBinaryOperation(..) => "InvalidBinaryOperation",
+            RuntimeError::InvalidUnaryOperation(..) => "InvalidUnaryOperation",
+            RuntimeError::InvalidOperandType(..) => "InvalidOperandType",
+            RuntimeError::UndefinedVariable(..) => "UndefinedVariable",
+            RuntimeError::InvalidLiteral(..) => "InvalidLiteral",
+            RuntimeError::InvalidLogicalOperation(..) => "InvalidLogicalOperation",
+            RuntimeError::InvalidFunctionCall(..) => "InvalidFunctionCall",
+            RuntimeError::ArityMismatch(..) => "ArityMismatch",
+            RuntimeError::UndefinedProperty(..) => "UndefinedProperty",
+            RuntimeError::InvalidPropertyAccess(..) => "InvalidPropertyAccess",
+            RuntimeError::InvalidSuperclass(..) => "InvalidSuperclass",
+            RuntimeError::AssertionFailed(..) => "AssertionFailed",
+            RuntimeError::IndexOutOfRange(..) => "IndexOutOfRange",
+            RuntimeError::KeyNotFound(..) => "KeyNotFound",
+            RuntimeError::Thrown(..) => "Thrown",
+            RuntimeError::ImportError(..) => "ImportError",
+            RuntimeError::StackOverflow(..) => "StackOverflow",
+            RuntimeError::Traced(error, _) => error.kind(),
+        }
+    }
+
+    // the message without the `RuntimeError:` prefix, as seen by a script's catch block
+    pub fn message(&self) -> String {
+        match self {
+            RuntimeError::Thrown(_, value) => value.to_string(),
+            RuntimeError::Traced(error, _) => error.message(),
+            _ => {
+                let rendered = self.to_string();
+                match rendered.strip_prefix("RuntimeError: ") {
+                    Some(message) => message.to_string(),
+                    None => rendered,
+                }
+            }
+        }
+    }
 }
 
 impl fmt::Display for RuntimeError {
@@ -52,7 +201,17 @@ impl fmt::Display for RuntimeError {
             RuntimeError::InvalidLiteral(token, msg) => write!(f, "RuntimeError: Invalid Literal at {}. {}", token.lexeme, msg),
             RuntimeError::InvalidLogicalOperation(token, msg) => write!(f, "RuntimeError: Invalid Logical Operation at {}. {}", token.lexeme, msg),
             RuntimeError::InvalidFunctionCall(token, msg) => write!(f, "RuntimeError: Invalid Function Call at {}. {}", token.lexeme, msg),
-            RuntimeError::Return(object) => write!(f, "Return {:?}", object),
+            RuntimeError::ArityMismatch(token, expected, got, _) => write!(f, "RuntimeError: Invalid Function Call at {}. Expected {} arguments but got {}", token.lexeme, expected, got),
+            RuntimeError::UndefinedProperty(token) => write!(f, "RuntimeError: Undefined Property {}.", token.lexeme),
+            RuntimeError::InvalidPropertyAccess(token, msg) => write!(f, "RuntimeError: Invalid Property Access at {}. {}", token.lexeme, msg),
+            RuntimeError::InvalidSuperclass(token, msg) => write!(f, "RuntimeError: Invalid Superclass {}. {}", token.lexeme, msg),
+            RuntimeError::AssertionFailed(token, msg) => write!(f, "RuntimeError: Assertion Failed at line {}. {}", token.line, msg),
+            RuntimeError::IndexOutOfRange(token, msg) => write!(f, "RuntimeError: Index Out Of Range at line {}. {}", token.line, msg),
+            RuntimeError::KeyNotFound(token, msg) => write!(f, "RuntimeError: Key Not Found at line {}. {}", token.line, msg),
+            RuntimeError::Thrown(token, value) => write!(f, "RuntimeError: Uncaught exception at line {}. {}", token.line, value),
+            RuntimeError::ImportError(token, msg) => write!(f, "RuntimeError: Import Error at line {}. {}", token.line, msg),
+            RuntimeError::StackOverflow(token) => write!(f, "RuntimeError: Stack Overflow at line {}. Maximum call depth exceeded", token.line),
+            RuntimeError::Traced(error, _) => write!(f, "{}", error),
         }
     }
 }
\ No newline at end of file
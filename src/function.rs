@@ -1,8 +1,8 @@
 use crate::ast::stmt::Stmt;
 use crate::error::RuntimeError;
 use crate::scope::Scope;
-use crate::token::Token;
-use crate::{interpreter::Interpreter, object::Object};
+use crate::token::{Token, TokenType};
+use crate::{interpreter::{Flow, Interpreter}, object::Object};
 use std::fmt;
 use std::rc::Rc;
 use std::cell::RefCell;
@@ -13,33 +13,81 @@ pub struct Function {
     params: Vec<Token>,
     body: Vec<Stmt>,
     closure: Rc<RefCell<Scope>>,
+    // top-level scope of the file the function was declared in
+    globals: Rc<RefCell<Scope>>,
+    is_initializer: bool,
 }
 
 impl Function {
-    pub fn new(name: Token, params: Vec<Token>, body: Vec<Stmt>, scope : Rc<RefCell<Scope>>) -> Function {
-        Function { name, params, body, closure: scope }
+    pub fn new(name: Token, params: Vec<Token>, body: Vec<Stmt>, scope : Rc<RefCell<Scope>>, globals: Rc<RefCell<Scope>>, is_initializer: bool) -> Function {
+        Function { name, params, body, closure: scope, globals, is_initializer }
     }
 
+    // Runs the body, then keeps running whatever it tail-calls in the same frame,
+    // so `return f(x);` recursion does not grow the stack.
     pub fn call(&mut self, interp: &mut Interpreter, args: Vec<Object>) -> Result<Object, RuntimeError> {
-        let mut scope = Rc::new(RefCell::new(Scope::from(&self.closure)));
-        for i in 0..args.len() {
-            scope.borrow_mut().define(format!("arg{}", i), args[i].clone());
+        let mut flow = self.invoke(interp, args)?;
+        while let Flow::TailCall(mut next, args) = flow {
+            interp.replace_frame(next.name());
+            flow = next.invoke(interp, args)?;
+        }
+        match flow {
+            Flow::Return(value) => Ok(value),
+            _ => Ok(Object::Null),
+        }
+    }
+
+    // the body's own result: `Flow::Return` with its value, or a `Flow::TailCall`
+    fn invoke(&mut self, interp: &mut Interpreter, args: Vec<Object>) -> Result<Flow, RuntimeError> {
+        let scope = Rc::new(RefCell::new(Scope::from(&self.closure)));
+        for (param, arg) in self.params.iter().zip(args) {
+            scope.borrow_mut().define(param.lexeme.clone(), arg);
         }
 
-        match (*interp).execute_block(&mut self.body, scope) {
-            Ok(obj) => obj,
-            Err(e) => match e {
-                RuntimeError::Return(obj) => return Ok(obj),
-                _ => return Err(e),
-            },
-        };
-        
-        unimplemented!()
+        // unresolved names inside the body refer to the declaring file's globals,
+        // and try blocks of the caller do not enclose it
+        let previous_globals = std::mem::replace(&mut interp.globals, Rc::clone(&self.globals));
+        let previous_try_depth = std::mem::replace(&mut interp.try_depth, 0);
+        let result = (*interp).execute_block(&self.body, scope);
+        interp.try_depth = previous_try_depth;
+        interp.globals = previous_globals;
+
+        // an initializer always hands back the instance, even on a bare `return;`
+        if self.is_initializer {
+            result?;
+            return Ok(Flow::Return(self.closure.borrow().get_at(0, &self.this_token())?));
+        }
+        match result? {
+            flow @ (Flow::Return(_) | Flow::TailCall(..)) => Ok(flow),
+            _ => Ok(Flow::Return(Object::Null)),
+        }
+    }
+
+    pub fn bind(&self, instance: Object) -> Function {
+        let scope = Rc::new(RefCell::new(Scope::from(&self.closure)));
+        scope.borrow_mut().define("this".to_string(), instance);
+        Function::new(
+            self.name.clone(),
+            self.params.clone(),
+            self.body.clone(),
+            scope,
+            Rc::clone(&self.globals),
+            self.is_initializer,
+        )
+    }
+
+    // the declaration's name token, which diagnostics point back at
+    pub fn name(&self) -> &Token {
+        &self.name
     }
 
     pub fn arity(&self) -> usize {
         self.params.len()
     }
+
+    fn this_token(&self) -> Token {
+        Token::new(TokenType::THIS, "this".to_string(), self.name.line)
+    }
 }
 
 impl fmt::Display for Function {
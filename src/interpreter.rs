@@ -1,28 +1,107 @@
-use std::f64::consts::PI;
-
 use crate::ast::expr;
 use crate::ast::expr::Expr;
 use crate::ast::stmt;
 use crate::ast::stmt::Stmt;
-use crate::error::{ParserError, RuntimeError};
+use crate::class::{Class, Instance, Method};
+use crate::diagnostic::SourceMap;
+use crate::error::RuntimeError;
 use crate::function::Function;
+use crate::list;
+use crate::map::{self, Map};
+use crate::ops;
+use crate::module::{self, Imports, Module};
+use crate::native::{self, NativeFn, NativeFunction, Runtime};
 use crate::object::Object;
+use crate::optimizer;
+use crate::resolver::Resolver;
 use crate::scope::Scope;
 use crate::token::{Token, TokenType};
 use std::cell::RefCell;
+use std::collections::HashMap;
+use std::path::PathBuf;
 use std::rc::Rc;
 
+// How a statement finished. `break`, `continue` and `return` travel back up to
+// the enclosing loop or call as ordinary values rather than as errors, so only
+// real errors are ever caught or traced.
+#[derive(Debug, Clone)]
+pub enum Flow {
+    Normal,
+    Break,
+    Continue,
+    Return(Object),
+    // `return f(x);` asking the running `Function::call` to make the call in its place
+    TailCall(Function, Vec<Object>),
+}
+
+// One active call: what was called and the call expression that called it.
+// A tail call reuses its caller's frame, so `function` is whatever is running
+// now and `elided` counts the calls that were replaced along the way.
+#[derive(Debug, Clone)]
+pub struct Frame {
+    pub function: String,
+    pub call_site: Token,
+    pub elided: usize,
+}
+
+// how deep calls may nest before raising `StackOverflow`, unless `SALMON_MAX_DEPTH` says otherwise
+pub const DEFAULT_MAX_CALL_DEPTH: usize = 1000;
+
 pub struct Interpreter {
+    // natives, shared by every file; each file's globals are a child of this
+    builtins: Rc<RefCell<Scope>>,
     pub globals: Rc<RefCell<Scope>>,
     scope: Rc<RefCell<Scope>>,
+    locals: HashMap<usize, usize>,
+    error_class: Rc<Class>,
+    // the directory imports resolve against, and the imports in progress
+    pub imports: Imports,
+    modules: HashMap<PathBuf, Object>,
+    // text of every file run so far, so errors can show the offending line
+    pub sources: SourceMap,
+    call_stack: Vec<Frame>,
+    max_call_depth: usize,
+    // how many try blocks of the running function enclose the current statement
+    pub try_depth: usize,
 }
 
 impl Interpreter {
     pub fn new() -> Interpreter {
-        Interpreter {
-            globals: Rc::new(RefCell::new(Scope::new())),
-            scope: Rc::new(RefCell::new(Scope::new())),
+        let builtins = Rc::new(RefCell::new(Scope::new()));
+        let globals = Rc::new(RefCell::new(Scope::from(&builtins)));
+        let mut interpreter = Interpreter {
+            builtins,
+            scope: Rc::clone(&globals),
+            globals,
+            locals: HashMap::new(),
+            error_class: Rc::new(Class::new("Error".to_string(), None, HashMap::new())),
+            imports: Imports::new(),
+            modules: HashMap::new(),
+            sources: SourceMap::new(),
+            call_stack: Vec::new(),
+            max_call_depth: DEFAULT_MAX_CALL_DEPTH,
+            try_depth: 0,
+        };
+        for func in native::prelude() {
+            interpreter.builtins.borrow_mut().define(
+                func.name.clone(),
+                Object::Native { func },
+            );
         }
+        interpreter
+    }
+
+    pub fn define_native(&mut self, name: &str, arity: usize, func: NativeFn) {
+        self.builtins.borrow_mut().define(
+            name.to_string(),
+            Object::Native {
+                func: NativeFunction::new(name, arity, func),
+            },
+        );
+    }
+
+    pub fn resolve(&mut self, id: usize, depth: usize) {
+        self.locals.insert(id, depth);
     }
 
     pub fn interpret(&mut self, stmts: &Vec<Stmt>) -> Result<(), RuntimeError> {
@@ -37,242 +116,192 @@ impl Interpreter {
     }
 
     pub fn check_truthy(&self, object: Object) -> bool {
-        match object {
-            Object::Boolean { value } => value,
-            Object::Null => false,
-            _ => return true,
-        }
-    }
-
-    fn check_equality(&self, left: &Object, right: &Object) -> bool {
-        left.eq(right)
+        object.is_truthy()
     }
 
-    pub fn execute_block(&mut self, stmts: &Vec<Stmt>, scope: Rc<RefCell<Scope>>) -> Result<(), RuntimeError> {
+    pub fn execute_block(&mut self, stmts: &Vec<Stmt>, scope: Rc<RefCell<Scope>>) -> Result<Flow, RuntimeError> {
         let previous = self.scope.clone();
         self.scope = scope;
+        let result = self.execute_stmts(stmts);
+        self.scope = previous;
+        result
+    }
+
+    fn execute_stmts(&mut self, stmts: &Vec<Stmt>) -> Result<Flow, RuntimeError> {
         for stmt in stmts {
-            self.execute(stmt)?
+            let flow = self.execute(stmt)?;
+            if !matches!(flow, Flow::Normal) {
+                return Ok(flow);
+            }
         }
-        self.scope = previous;
-        Ok(())
+        Ok(Flow::Normal)
+    }
+
+    pub fn set_max_call_depth(&mut self, depth: usize) {
+        self.max_call_depth = depth;
     }
 
-    pub fn execute(&mut self, stmt: &Stmt) -> Result<(), RuntimeError> {
+    // the calls in progress, outermost first
+    pub fn call_stack(&self) -> &[Frame] {
+        &self.call_stack
+    }
+
+    pub fn execute(&mut self, stmt: &Stmt) -> Result<Flow, RuntimeError> {
         return stmt.accept(self);
     }
 
-    // fn lookup_variable(&self, name : &Token, expr : Expr) -> Result<Object, RuntimeError> {
-    //     let distance = locals.get(name);
+    fn import_module(&mut self, keyword: &Token, path: &Token) -> Result<Object, RuntimeError> {
+        let import_error = |msg: String| RuntimeError::ImportError((*keyword).clone(), msg);
 
-    //     if let Some(dist) = distance {
-    //         return self.scope.get_at(*dist, name);
-    //     } else {
-    //         return self.scope.get(name);
-    //     }
-    // }
-}
+        let canonical = module::locate(&self.imports.current_dir, path.value()).map_err(import_error)?;
+        if let Some(module) = self.modules.get(&canonical) {
+            return Ok(module.clone());
+        }
+        module::check_cycle(&self.imports.importing, &canonical).map_err(import_error)?;
 
-impl expr::Visitor<Result<Object, RuntimeError>> for Interpreter {
-    fn visit_unary(&mut self, op: &Token, value: &Box<Expr>) -> Result<Object, RuntimeError> {
-        let right: Object = self.evaluate(value)?;
+        let stmts = module::parse(&canonical, path.value(), &mut self.sources).map_err(import_error)?;
+        if let Err(errors) = Resolver::new(self).resolve(&stmts) {
+            return Err(import_error(module::resolve_failure(path.value(), &errors)));
+        }
+        let stmts = optimizer::optimize(stmts);
 
-        match op.token_type {
-            TokenType::MINUS => match right {
-                Object::Number { value } => return Ok(Object::Number { value: -value }),
-                _ => {
-                    return Err(RuntimeError::InvalidUnaryOperation(
-                        (*op).clone(),
-                        "Operator can only be used on numbers".to_string(),
-                    ))
-                }
-            },
+        // run the module in a fresh top-level scope, then put everything back
+        let module_scope = Rc::new(RefCell::new(Scope::from(&self.builtins)));
+        let previous_globals = std::mem::replace(&mut self.globals, Rc::clone(&module_scope));
+        let previous_scope = std::mem::replace(&mut self.scope, Rc::clone(&module_scope));
+        let previous_dir = self.imports.enter(&canonical);
+
+        let result = self.interpret(&stmts);
+
+        self.imports.leave(previous_dir);
+        self.scope = previous_scope;
+        self.globals = previous_globals;
+        result?;
+
+        let name = module::default_name(path.value()).unwrap_or_else(|| path.value().to_string());
+        let module = Object::Module {
+            module: Rc::new(Module::new(name, canonical.clone(), module_scope)),
+        };
+        self.modules.insert(canonical, module.clone());
+        Ok(module)
+    }
 
-            TokenType::BANG => Ok(Object::Boolean {
-                value: !self.check_truthy(right),
-            }),
+    fn evaluate_call(&mut self, callee: &Expr, arguments: &Vec<Expr>) -> Result<(Object, Vec<Object>), RuntimeError> {
+        let callee: Object = self.evaluate(callee)?;
+        let mut args: Vec<Object> = Vec::new();
+        for arg in arguments {
+            args.push(self.evaluate(arg)?);
+        }
+        Ok((callee, args))
+    }
 
+    fn call(&mut self, callee: Object, args: Vec<Object>, paren: &Token, call_site: Token) -> Result<Object, RuntimeError> {
+        let (name, min_arity, arity, declaration) = match &callee {
+            Object::Callable { func } => (func.name().lexeme.clone(), func.arity(), func.arity(), Some(func.name().clone())),
+            Object::Native { func } => (func.name.clone(), func.min_arity(), func.arity(), None),
+            Object::Class { class } => (
+                class.name.clone(),
+                class.arity(),
+                class.arity(),
+                class.find_method("init").map(|init| init.name().clone()),
+            ),
             _ => {
-                return Err(RuntimeError::InvalidUnaryOperation(
-                    (*op).clone(),
-                    "".to_string(),
+                return Err(RuntimeError::InvalidFunctionCall(
+                    (*paren).clone(),
+                    "Can only call functions and classes".to_string(),
                 ))
             }
+        };
+        if args.len() < min_arity || args.len() > arity {
+            return Err(RuntimeError::ArityMismatch(call_site, arity, args.len(), declaration));
         }
-    }
 
-    fn visit_binary(
-        &mut self,
-        op: &Token,
-        left: &Box<Expr>,
-        right: &Box<Expr>,
-    ) -> Result<Object, RuntimeError> {
-        let right: Object = self.evaluate(right)?;
-        let left: Object = self.evaluate(left)?;
-
-        match op.token_type {
-            TokenType::AND => {
-                if !self.check_truthy(left) {
-                    return Ok(Object::Boolean { value: false });
-                }
-                return Ok(Object::Boolean {
-                    value: self.check_truthy(right),
-                });
-            }
+        if self.call_stack.len() >= self.max_call_depth {
+            return Err(RuntimeError::StackOverflow(call_site));
+        }
 
-            TokenType::OR => {
-                if self.check_truthy(left) {
-                    return Ok(Object::Boolean { value: true });
+        self.call_stack.push(Frame {
+            function: name,
+            call_site,
+            elided: 0,
+        });
+        let result = match callee {
+            Object::Callable { func: mut f } => f.call(self, args),
+            Object::Native { func } => func.call(self, paren, args),
+            Object::Class { class } => {
+                let instance = Object::Instance {
+                    instance: Rc::new(RefCell::new(Instance::new(Rc::clone(&class)))),
+                };
+                match class.find_method("init") {
+                    Some(Method::Function(init)) => init.bind(instance.clone()).call(self, args).map(|_| instance),
+                    _ => Ok(instance),
                 }
-                return Ok(Object::Boolean {
-                    value: self.check_truthy(right),
-                });
             }
+            _ => unreachable!(),
+        };
+        // taken from the stack rather than kept from above, since tail calls update it
+        let frame = self.call_stack.pop().unwrap();
+        result.map_err(|error| error.with_frame(frame))
+    }
 
-            TokenType::MINUS => match (left, right) {
-                (Object::Number { value: l }, Object::Number { value: r }) => {
-                    return Ok(Object::Number { value: l - r })
-                }
-                _ => {
-                    return Err(RuntimeError::InvalidBinaryOperation(
-                        (*op).clone(),
-                        "Operator can only be used on numbers".to_string(),
-                    ))
-                }
-            },
-
-            TokenType::PLUS => match (left, right) {
-                (Object::Number { value: l }, Object::Number { value: r }) => {
-                    return Ok(Object::Number { value: l + r })
-                }
-                (Object::String { value: l }, Object::String { value: r }) => {
-                    return Ok(Object::String {
-                        value: l.clone() + &r,
-                    })
-                }
-                _ => {
-                    return Err(RuntimeError::InvalidBinaryOperation(
-                        (*op).clone(),
-                        "Operator can only be used on numbers or strings".to_string(),
-                    ))
-                }
-            },
-
-            TokenType::GREATER => match (left, right) {
-                (Object::Number { value: l }, Object::Number { value: r }) => {
-                    return Ok(Object::Boolean { value: l > r })
-                }
-                _ => {
-                    return Err(RuntimeError::InvalidBinaryOperation(
-                        (*op).clone(),
-                        "Operator can only be used on numbers".to_string(),
-                    ))
-                }
-            },
-
-            TokenType::GREATER_EQUAL => match (left, right) {
-                (Object::Number { value: l }, Object::Number { value: r }) => {
-                    return Ok(Object::Boolean { value: l >= r })
-                }
-                _ => {
-                    return Err(RuntimeError::InvalidBinaryOperation(
-                        (*op).clone(),
-                        "Operator can only be used on numbers".to_string(),
-                    ))
-                }
-            },
+    // a tail call replaces the frame of the function it returns from, see `Function::call`
+    pub fn replace_frame(&mut self, function: &Token) {
+        if let Some(frame) = self.call_stack.last_mut() {
+            frame.function = function.lexeme.clone();
+            frame.elided += 1;
+        }
+    }
 
-            TokenType::LESS => match (left, right) {
-                (Object::Number { value: l }, Object::Number { value: r }) => {
-                    return Ok(Object::Boolean { value: l < r })
-                }
-                _ => {
-                    return Err(RuntimeError::InvalidBinaryOperation(
-                        (*op).clone(),
-                        "Operator can only be used on numbers".to_string(),
-                    ))
-                }
-            },
+    fn assign_variable(&mut self, name: &Token, id: usize, value: Object) -> Result<(), RuntimeError> {
+        match self.locals.get(&id) {
+            Some(dist) => self.scope.borrow_mut().assign_at(*dist, name, value),
+            None => self.globals.borrow_mut().assign(name, value),
+        }
+    }
 
-            TokenType::LESS_EQUAL => match (left, right) {
-                (Object::Number { value: l }, Object::Number { value: r }) => {
-                    return Ok(Object::Boolean { value: l <= r })
-                }
-                _ => {
-                    return Err(RuntimeError::InvalidBinaryOperation(
-                        (*op).clone(),
-                        "Operator can only be used on numbers".to_string(),
-                    ))
-                }
-            },
+    fn lookup_variable(&self, name: &Token, id: usize) -> Result<Object, RuntimeError> {
+        let distance = self.locals.get(&id);
 
-            TokenType::SLASH => match (left, right) {
-                (Object::Number { value: l }, Object::Number { value: r }) => {
-                    return Ok(Object::Number { value: l / r })
-                }
-                _ => {
-                    return Err(RuntimeError::InvalidBinaryOperation(
-                        (*op).clone(),
-                        "Operator can only be used on numbers".to_string(),
-                    ))
-                }
-            },
+        if let Some(dist) = distance {
+            return self.scope.borrow().get_at(*dist, name);
+        } else {
+            return self.globals.borrow().get(name);
+        }
+    }
+}
 
-            TokenType::STAR => match (left, right) {
-                (Object::Number { value: l }, Object::Number { value: r }) => {
-                    return Ok(Object::Number { value: l * r })
-                }
-                _ => {
-                    return Err(RuntimeError::InvalidBinaryOperation(
-                        (*op).clone(),
-                        "Operator can only be used on numbers".to_string(),
-                    ))
-                }
-            },
+// the closing paren, widened to cover the whole call for error reporting
+fn call_site(callee: &Expr, paren: &Token) -> Token {
+    let mut call_site = (*paren).clone();
+    call_site.span = callee.span().to(&paren.span);
+    call_site
+}
 
-            TokenType::EQUAL_EQUAL => {
-                return Ok(Object::Boolean {
-                    value: self.check_equality(&left, &right),
-                });
-            }
+impl Runtime for Interpreter {
+    fn call_stack(&self) -> &[Frame] {
+        &self.call_stack
+    }
+}
 
-            TokenType::BANG_EQUAL => {
-                return Ok(Object::Boolean {
-                    value: !self.check_equality(&left, &right),
-                });
-            }
+impl expr::Visitor<Result<Object, RuntimeError>> for Interpreter {
+    fn visit_unary(&mut self, op: &Token, value: &Box<Expr>) -> Result<Object, RuntimeError> {
+        let right: Object = self.evaluate(value)?;
+        ops::unary(op, right)
+    }
 
-            _ => {
-                return Err(RuntimeError::InvalidBinaryOperation(
-                    (*op).clone(),
-                    "".to_string(),
-                ))
-            }
-        }
+    fn visit_binary(
+        &mut self,
+        op: &Token,
+        left: &Box<Expr>,
+        right: &Box<Expr>,
+    ) -> Result<Object, RuntimeError> {
+        let right: Object = self.evaluate(right)?;
+        let left: Object = self.evaluate(left)?;
+        ops::binary(op, left, right)
     }
 
     fn visit_literal(&mut self, value: &Token) -> Result<Object, RuntimeError> {
-        match value.token_type {
-            TokenType::NUMBER => {
-                return Ok(Object::Number {
-                    value: value.lexeme.parse::<f64>().unwrap(),
-                })
-            }
-            TokenType::STRING => {
-                return Ok(Object::String {
-                    value: value.lexeme.clone(),
-                })
-            }
-            TokenType::TRUE => return Ok(Object::Boolean { value: true }),
-            TokenType::FALSE => return Ok(Object::Boolean { value: false }),
-            TokenType::NULL => return Ok(Object::Null),
-            _ => {
-                return Err(RuntimeError::InvalidLiteral(
-                    (*value).clone(),
-                    "".to_string(),
-                ))
-            }
-        }
+        ops::literal(value)
     }
 
     fn visit_grouping(&mut self, expr: &Box<Expr>) -> Result<Object, RuntimeError> {
@@ -311,67 +340,253 @@ impl expr::Visitor<Result<Object, RuntimeError>> for Interpreter {
         }
     }
 
-    fn visit_variable(&mut self, name: &Token) -> Result<Object, RuntimeError> {
-        self.scope.borrow().get(name)
+    fn visit_variable(&mut self, name: &Token, id: usize) -> Result<Object, RuntimeError> {
+        self.lookup_variable(name, id)
     }
 
-    fn visit_assign(&mut self, name: &Token, value: &Box<Expr>) -> Result<Object, RuntimeError> {
+    fn visit_assign(&mut self, name: &Token, value: &Box<Expr>, id: usize) -> Result<Object, RuntimeError> {
         let value = self.evaluate(value)?;
-        self.scope.borrow_mut().assign(name, value.clone());
+        self.assign_variable(name, id, value.clone())?;
         return Ok(value);
     }
 
+    // the target's object and index are evaluated before `value`, and each only once
+    fn visit_update(
+        &mut self,
+        target: &Box<Expr>,
+        op: &Token,
+        value: &Box<Expr>,
+        postfix: bool,
+    ) -> Result<Object, RuntimeError> {
+        let (old, new) = match &**target {
+            Expr::Variable { name, id, .. } => {
+                let value = self.evaluate(value)?;
+                let old = self.lookup_variable(name, *id)?;
+                let new = ops::binary(op, old.clone(), value)?;
+                self.assign_variable(name, *id, new.clone())?;
+                (old, new)
+            }
+            Expr::Get { object, name, .. } => {
+                let object = self.evaluate(object)?;
+                ops::check_field_target(&object, name)?;
+                let value = self.evaluate(value)?;
+                let old = ops::get_property(object.clone(), name)?;
+                let new = ops::binary(op, old.clone(), value)?;
+                if let Object::Instance { instance } = object {
+                    instance.borrow_mut().set(name, new.clone());
+                }
+                (old, new)
+            }
+            Expr::Index { object, bracket, index, .. } => {
+                let object = self.evaluate(object)?;
+                let index = self.evaluate(index)?;
+                let value = self.evaluate(value)?;
+                let old = ops::index(object.clone(), &index, bracket)?;
+                let new = ops::binary(op, old.clone(), value)?;
+                ops::set_index(object, index, new.clone(), bracket)?;
+                (old, new)
+            }
+            _ => unreachable!("the parser only builds updates of variables, properties and indexes"),
+        };
+        Ok(if postfix { old } else { new })
+    }
+
     fn visit_call(
         &mut self,
         callee: &Box<Expr>,
         paren: &Token,
         arguments: &Vec<Expr>,
     ) -> Result<Object, RuntimeError> {
-        let callee: Object = self.evaluate(callee)?;
-        let mut args: Vec<Object> = Vec::new();
-        for arg in arguments {
-            args.push(self.evaluate(arg)?);
+        let call_site = call_site(callee, paren);
+        let (callee, args) = self.evaluate_call(callee, arguments)?;
+        self.call(callee, args, paren, call_site)
+    }
+
+    fn visit_get(&mut self, object: &Box<Expr>, name: &Token) -> Result<Object, RuntimeError> {
+        let object = self.evaluate(object)?;
+        ops::get_property(object, name)
+    }
+
+    fn visit_set(
+        &mut self,
+        object: &Box<Expr>,
+        name: &Token,
+        value: &Box<Expr>,
+    ) -> Result<Object, RuntimeError> {
+        let object = self.evaluate(object)?;
+        ops::check_field_target(&object, name)?;
+        let value = self.evaluate(value)?;
+        if let Object::Instance { instance } = object {
+            instance.borrow_mut().set(name, value.clone());
         }
+        Ok(value)
+    }
 
-        match callee {
-            Object::Callable { func: mut f } => {
-                if args.len() != f.arity() {
-                    return Err(RuntimeError::InvalidFunctionCall(
-                        (*paren).clone(),
-                        "Incorrect number of arguments".to_string(),
-                    ));
-                } else {
-                    return f.call(self, args);
-                }
+    fn visit_list(&mut self, bracket: &Token, elements: &Vec<Expr>) -> Result<Object, RuntimeError> {
+        let mut values: Vec<Object> = Vec::new();
+        for element in elements {
+            values.push(self.evaluate(element)?);
+        }
+        Ok(list::new_list(values))
+    }
+
+    fn visit_map(&mut self, brace: &Token, entries: &Vec<(Expr, Expr)>) -> Result<Object, RuntimeError> {
+        let mut map = Map::new();
+        for (key, value) in entries {
+            let key = self.evaluate(key)?;
+            map::check_key(&key, brace)?;
+            let value = self.evaluate(value)?;
+            map.insert(key, value);
+        }
+        Ok(map::new_map(map))
+    }
+
+    fn visit_index(
+        &mut self,
+        object: &Box<Expr>,
+        bracket: &Token,
+        index: &Box<Expr>,
+    ) -> Result<Object, RuntimeError> {
+        let object = self.evaluate(object)?;
+        let index = self.evaluate(index)?;
+        ops::index(object, &index, bracket)
+    }
+
+    fn visit_set_index(
+        &mut self,
+        object: &Box<Expr>,
+        bracket: &Token,
+        index: &Box<Expr>,
+        value: &Box<Expr>,
+    ) -> Result<Object, RuntimeError> {
+        let object = self.evaluate(object)?;
+        let index = self.evaluate(index)?;
+        let value = self.evaluate(value)?;
+        ops::set_index(object, index, value, bracket)
+    }
+
+    fn visit_interpolation(&mut self, start: &Token, parts: &Vec<Expr>) -> Result<Object, RuntimeError> {
+        let mut values: Vec<Object> = Vec::new();
+        for part in parts {
+            values.push(self.evaluate(part)?);
+        }
+        Ok(ops::interpolate(values))
+    }
+
+    fn visit_lambda(&mut self, name: &Token, params: &Vec<Token>, body: &Vec<Stmt>) -> Result<Object, RuntimeError> {
+        Ok(Object::Callable {
+            func: Function::new(
+                name.clone(),
+                params.clone(),
+                body.clone(),
+                Rc::clone(&self.scope),
+                Rc::clone(&self.globals),
+                false,
+            ),
+        })
+    }
+
+    fn visit_this(&mut self, keyword: &Token, id: usize) -> Result<Object, RuntimeError> {
+        self.lookup_variable(keyword, id)
+    }
+
+    fn visit_super(&mut self, keyword: &Token, method: &Token, id: usize) -> Result<Object, RuntimeError> {
+        let distance = match self.locals.get(&id) {
+            Some(dist) => *dist,
+            None => {
+                return Err(RuntimeError::InvalidSuperclass(
+                    (*keyword).clone(),
+                    "Can only use 'super' inside a subclass".to_string(),
+                ))
             }
+        };
+        let superclass = match self.scope.borrow().get_at(distance, keyword)? {
+            Object::Class { class } => class,
             _ => {
-                return Err(RuntimeError::InvalidFunctionCall(
-                    (*paren).clone(),
-                    "Can only call functions".to_string(),
+                return Err(RuntimeError::InvalidSuperclass(
+                    (*keyword).clone(),
+                    "Can only use 'super' inside a subclass".to_string(),
                 ))
             }
         };
+        // `this` is always bound one scope inside the one holding `super`
+        let this = Token::new(TokenType::THIS, "this".to_string(), keyword.line);
+        let instance = self.scope.borrow().get_at(distance - 1, &this)?;
+
+        match superclass.find_method(&method.lexeme) {
+            Some(func) => Ok(func.bind(instance)),
+            None => Err(RuntimeError::UndefinedProperty((*method).clone())),
+        }
     }
 }
 
-impl stmt::Visitor<Result<(), RuntimeError>> for Interpreter {
-    fn visit_var(&mut self, name: &Token, initializer: &Option<Expr>) -> Result<(), RuntimeError> {
+impl stmt::Visitor<Result<Flow, RuntimeError>> for Interpreter {
+    fn visit_throw(&mut self, keyword: &Token, value: &Expr) -> Result<Flow, RuntimeError> {
+        let value = self.evaluate(value)?;
+        Err(RuntimeError::thrown(keyword, value))
+    }
+
+    fn visit_try(
+        &mut self,
+        body: &Vec<Stmt>,
+        catch_name: &Option<Token>,
+        catch_body: &Option<Vec<Stmt>>,
+        finally_body: &Option<Vec<Stmt>>,
+    ) -> Result<Flow, RuntimeError> {
+        self.try_depth += 1;
+        let mut result = self.execute_block(body, Rc::new(RefCell::new(Scope::from(&self.scope))));
+        self.try_depth -= 1;
+
+        if let Some(catch_body) = catch_body {
+            if let Err(error) = result {
+                let scope = Rc::new(RefCell::new(Scope::from(&self.scope)));
+                if let Some(name) = catch_name {
+                    scope.borrow_mut().define(name.lexeme.clone(), error.to_object(&self.error_class));
+                }
+                // errors from the catch block still have to pass through finally
+                let protected = finally_body.is_some() as usize;
+                self.try_depth += protected;
+                result = self.execute_block(catch_body, scope);
+                self.try_depth -= protected;
+            }
+        }
+
+        // finally runs on every exit path; if it exits abruptly itself, that wins
+        if let Some(finally_body) = finally_body {
+            let flow = self.execute_block(
+                finally_body,
+                Rc::new(RefCell::new(Scope::from(&self.scope))),
+            )?;
+            if !matches!(flow, Flow::Normal) {
+                return Ok(flow);
+            }
+        }
+
+        result
+    }
+
+    fn visit_var(&mut self, name: &Token, initializer: &Option<Expr>) -> Result<Flow, RuntimeError> {
         let value: Object = match initializer {
             Some(expr) => self.evaluate(expr)?,
             None => Object::Null,
         };
         self.scope.borrow_mut().define(name.clone().lexeme, value);
-        Ok(())
+        Ok(Flow::Normal)
     }
 
-    fn visit_block(&mut self, statements: &Vec<Stmt>) -> Result<(), RuntimeError> {
-        self.execute_block(statements, Rc::new(RefCell::new(Scope::from(&self.scope))));
-        Ok(())
+    fn visit_block(&mut self, statements: &Vec<Stmt>) -> Result<Flow, RuntimeError> {
+        self.execute_block(statements, Rc::new(RefCell::new(Scope::from(&self.scope))))
     }
 
-    fn visit_expression(&mut self, expr: &Expr) -> Result<(), RuntimeError> {
+    fn visit_expression(&mut self, expr: &Expr) -> Result<Flow, RuntimeError> {
         self.evaluate(expr)?;
-        Ok(())
+        Ok(Flow::Normal)
+    }
+
+    fn visit_import(&mut self, keyword: &Token, path: &Token, name: &Token) -> Result<Flow, RuntimeError> {
+        let module = self.import_module(keyword, path)?;
+        self.scope.borrow_mut().define(name.lexeme.clone(), module);
+        Ok(Flow::Normal)
     }
 
     fn visit_if(
@@ -379,43 +594,136 @@ impl stmt::Visitor<Result<(), RuntimeError>> for Interpreter {
         condition: &Expr,
         then_branch: &Stmt,
         else_branch: &Option<Box<Stmt>>,
-    ) -> Result<(), RuntimeError> {
+    ) -> Result<Flow, RuntimeError> {
         let cond = self.evaluate(condition)?;
         if self.check_truthy(cond) {
-            self.execute(then_branch)?;
+            self.execute(then_branch)
         } else if let Some(else_branch) = else_branch {
-            self.execute(else_branch)?;
+            self.execute(else_branch)
+        } else {
+            Ok(Flow::Normal)
         }
-
-        Ok(())
     }
 
-    fn visit_class(&mut self, name: &Token, methods: &Vec<Stmt>) -> Result<(), RuntimeError> {
-        unimplemented!()
+    fn visit_class(
+        &mut self,
+        name: &Token,
+        superclass: &Option<Expr>,
+        methods: &Vec<Stmt>,
+    ) -> Result<Flow, RuntimeError> {
+        let superclass: Option<Rc<Class>> = match superclass {
+            Some(expr) => match self.evaluate(expr)? {
+                Object::Class { class } => Some(class),
+                other => {
+                    let token = match expr {
+                        Expr::Variable { name, .. } => name.clone(),
+                        _ => name.clone(),
+                    };
+                    return Err(RuntimeError::InvalidSuperclass(
+                        token,
+                        format!("Superclass must be a class, found {}", other),
+                    ));
+                }
+            },
+            None => None,
+        };
+
+        // methods of a subclass close over a scope that binds `super`
+        let mut method_scope = Rc::clone(&self.scope);
+        if let Some(superclass) = &superclass {
+            method_scope = Rc::new(RefCell::new(Scope::from(&self.scope)));
+            method_scope.borrow_mut().define(
+                "super".to_string(),
+                Object::Class {
+                    class: Rc::clone(superclass),
+                },
+            );
+        }
+
+        let mut class_methods: HashMap<String, Method> = HashMap::new();
+        for method in methods {
+            if let Stmt::Function { name, params, body, .. } = method {
+                let func = Function::new(
+                    name.clone(),
+                    params.clone(),
+                    body.clone(),
+                    Rc::clone(&method_scope),
+                    Rc::clone(&self.globals),
+                    name.lexeme == "init",
+                );
+                class_methods.insert(name.lexeme.clone(), Method::Function(func));
+            }
+        }
+
+        let class = Object::Class {
+            class: Rc::new(Class::new(name.lexeme.clone(), superclass, class_methods)),
+        };
+        self.scope.borrow_mut().define(name.lexeme.clone(), class);
+        Ok(Flow::Normal)
     }
 
-    fn visit_print(&mut self, expr: &Expr) -> Result<(), RuntimeError> {
+    fn visit_print(&mut self, expr: &Expr) -> Result<Flow, RuntimeError> {
         println!("{}", self.evaluate(expr)?);
-        Ok(())
+        Ok(Flow::Normal)
     }
 
-    fn visit_while(&mut self, condition: &Expr, body: &Stmt) -> Result<(), RuntimeError> {
+    fn visit_while(
+        &mut self,
+        condition: &Expr,
+        body: &Stmt,
+        increment: &Option<Expr>,
+        per_iteration: bool,
+    ) -> Result<Flow, RuntimeError> {
         let mut value = self.evaluate(condition)?;
         while self.check_truthy(value) {
-            self.execute(body)?;
+            match self.execute(body)? {
+                Flow::Normal | Flow::Continue => {}
+                Flow::Break => break,
+                flow => return Ok(flow),
+            }
+            // the loop variable lives alone in the scope around the loop; closures
+            // made in the body keep the old one
+            if per_iteration {
+                let next = self.scope.borrow().clone();
+                self.scope = Rc::new(RefCell::new(next));
+            }
+            if let Some(increment) = increment {
+                self.evaluate(increment)?;
+            }
             value = self.evaluate(condition)?;
         }
 
-        Ok(())
+        Ok(Flow::Normal)
     }
 
-    fn visit_return(&mut self, keyword: &Token, value: &Option<Expr>) -> Result<(), RuntimeError> {
+    fn visit_break(&mut self, keyword: &Token) -> Result<Flow, RuntimeError> {
+        Ok(Flow::Break)
+    }
+
+    fn visit_continue(&mut self, keyword: &Token) -> Result<Flow, RuntimeError> {
+        Ok(Flow::Continue)
+    }
+
+    fn visit_return(&mut self, keyword: &Token, value: &Option<Expr>) -> Result<Flow, RuntimeError> {
+        // `return f(x);` hands the call back to `Function::call` to make in place of this one,
+        // unless a try around it still has to see what the call does
+        if let Some(Expr::Call { callee, paren, arguments }) = value {
+            if self.try_depth == 0 {
+                let call_site = call_site(callee, paren);
+                let (callee, args) = self.evaluate_call(callee, arguments)?;
+                return match callee {
+                    Object::Callable { func } if func.arity() == args.len() => Ok(Flow::TailCall(func, args)),
+                    callee => Ok(Flow::Return(self.call(callee, args, paren, call_site)?)),
+                };
+            }
+        }
+
         let val: Object = match value {
             Some(expr) => self.evaluate(expr)?,
             None => Object::Null,
         };
 
-        Err(RuntimeError::Return(val))
+        Ok(Flow::Return(val))
     }
 
     fn visit_function(
@@ -423,16 +731,18 @@ impl stmt::Visitor<Result<(), RuntimeError>> for Interpreter {
         name: &Token,
         params: &Vec<Token>,
         body: &Vec<Stmt>,
-    ) -> Result<(), RuntimeError> {
+    ) -> Result<Flow, RuntimeError> {
         let func = Object::Callable {
             func: Function::new(
                 name.clone(),
                 params.clone(),
                 body.clone(),
                 Rc::clone(&self.scope),
+                Rc::clone(&self.globals),
+                false,
             ),
         };
         self.scope.borrow_mut().define(name.lexeme.clone(), func);
-        Ok(())
+        Ok(Flow::Normal)
     }
 }
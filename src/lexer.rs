@@ -1,37 +1,73 @@
-use crate::error::error::report_error;
-use crate::token::{Token, TokenType};
+use crate::diagnostic::{Diagnostic, SourceMap};
+use crate::token::{Span, Token, TokenType};
 use std::iter::Peekable;
 use std::str::Chars;
 
 pub struct Lexer<'a> {
     source: Peekable<Chars<'a>>, // Use Peekable<Chars<'a>> for peeking
+    text: &'a str,
+    // index and name of the file in the run's `SourceMap`, stamped on every span
+    file: usize,
+    name: String,
     current_char: Option<char>,
     current_line: usize,
     current_column: usize,
+    current_offset: usize,
     tokens: Vec<Token>,
+    // for each `${` still open, innermost last: the delimiter of the string it
+    // interrupted and how many `{` inside the expression are still unclosed
+    interpolations: Vec<(&'static str, usize)>,
+    // set when the source ends inside a string
+    unterminated: bool,
+    // errors are still counted but not printed
+    quiet: bool,
     pub num_errors: usize,
 }
 
 impl<'a> Lexer<'a> {
     pub fn new(source: &'a str) -> Self {
+        Lexer::with_file(source, 0, "<input>")
+    }
+
+    pub fn with_file(source: &'a str, file: usize, name: &str) -> Self {
         let mut lexer = Lexer {
             source: source.chars().peekable(), // Convert to Peekable<Chars<'a>>
+            text: source,
+            file,
+            name: name.to_string(),
             current_char: None,
             current_line: 1,
             current_column: 1,
+            current_offset: 0,
             tokens: Vec::new(),
+            interpolations: Vec::new(),
+            unterminated: false,
+            quiet: false,
             num_errors: 0,
         };
         lexer.current_char = lexer.source.next();
         lexer
     }
 
+    pub fn quiet(mut self) -> Self {
+        self.quiet = true;
+        self
+    }
+
+    // whether the source stopped partway through a string or a `${...}` inside one
+    pub fn is_incomplete(&self) -> bool {
+        self.unterminated || !self.interpolations.is_empty()
+    }
+
     pub fn get_tokens(&self) -> Vec<Token> {
         self.tokens.clone()
     }
 
     pub fn tokenize(&mut self) -> Vec<Token> {
         while let Some(c) = self.current_char {
+            let mut start = self.current_offset;
+            let mut start_line = self.current_line;
+            let mut start_column = self.current_column;
             let token = match c {
                 '(' => Some(Token::new(
                     TokenType::LEFT_PAREN,
@@ -43,42 +79,174 @@ impl<'a> Lexer<'a> {
                     c.to_string(),
                     self.current_line,
                 )),
-                '{' => Some(Token::new(
-                    TokenType::LEFT_BRACE,
+                '{' => {
+                    if let Some((_, depth)) = self.interpolations.last_mut() {
+                        *depth += 1;
+                    }
+                    Some(Token::new(
+                        TokenType::LEFT_BRACE,
+                        c.to_string(),
+                        self.current_line,
+                    ))
+                }
+                // the `}` closing a `${` picks the string back up where it left off
+                '}' if self.interpolations.last().map_or(false, |&(_, depth)| depth == 0) => {
+                    let (delimiter, _) = self.interpolations.pop().unwrap();
+                    self.advance();
+                    self.string_body(delimiter, start, start_line, start_column)
+                }
+                '}' => {
+                    if let Some((_, depth)) = self.interpolations.last_mut() {
+                        *depth -= 1;
+                    }
+                    Some(Token::new(
+                        TokenType::RIGHT_BRACE,
+                        c.to_string(),
+                        self.current_line,
+                    ))
+                }
+                '[' => Some(Token::new(
+                    TokenType::LEFT_BRACKET,
                     c.to_string(),
                     self.current_line,
                 )),
-                '}' => Some(Token::new(
-                    TokenType::RIGHT_BRACE,
+                ']' => Some(Token::new(
+                    TokenType::RIGHT_BRACKET,
                     c.to_string(),
                     self.current_line,
                 )),
-                '*' => Some(Token::new(
-                    TokenType::STAR,
+                '*' => {
+                    if self.match_next('*') {
+                        self.advance();
+                        Some(Token::new(
+                            TokenType::STAR_STAR,
+                            "**".to_string(),
+                            self.current_line,
+                        ))
+                    } else if self.match_next('=') {
+                        self.advance();
+                        Some(Token::new(
+                            TokenType::STAR_EQUAL,
+                            "*=".to_string(),
+                            self.current_line,
+                        ))
+                    } else {
+                        Some(Token::new(
+                            TokenType::STAR,
+                            c.to_string(),
+                            self.current_line,
+                        ))
+                    }
+                }
+                '%' => {
+                    if self.match_next('=') {
+                        self.advance();
+                        Some(Token::new(
+                            TokenType::PERCENT_EQUAL,
+                            "%=".to_string(),
+                            self.current_line,
+                        ))
+                    } else {
+                        Some(Token::new(
+                            TokenType::PERCENT,
+                            c.to_string(),
+                            self.current_line,
+                        ))
+                    }
+                }
+                '&' => Some(Token::new(
+                    TokenType::AMPERSAND,
                     c.to_string(),
                     self.current_line,
                 )),
-                '.' => Some(Token::new(TokenType::DOT, c.to_string(), self.current_line)),
-                ',' => Some(Token::new(
-                    TokenType::COMMA,
+                '|' => Some(Token::new(
+                    TokenType::PIPE,
                     c.to_string(),
                     self.current_line,
                 )),
-                '+' => Some(Token::new(
-                    TokenType::PLUS,
+                '^' => Some(Token::new(
+                    TokenType::CARET,
                     c.to_string(),
                     self.current_line,
                 )),
-                ';' => Some(Token::new(
-                    TokenType::SEMICOLON,
+                '~' => {
+                    if self.match_next('/') {
+                        self.advance();
+                        Some(Token::new(
+                            TokenType::TILDE_SLASH,
+                            "~/".to_string(),
+                            self.current_line,
+                        ))
+                    } else {
+                        Some(Token::new(
+                            TokenType::TILDE,
+                            c.to_string(),
+                            self.current_line,
+                        ))
+                    }
+                }
+                '.' => Some(Token::new(TokenType::DOT, c.to_string(), self.current_line)),
+                ':' => Some(Token::new(
+                    TokenType::COLON,
                     c.to_string(),
                     self.current_line,
                 )),
-                '-' => Some(Token::new(
-                    TokenType::MINUS,
+                ',' => Some(Token::new(
+                    TokenType::COMMA,
                     c.to_string(),
                     self.current_line,
                 )),
+                '+' => {
+                    if self.match_next('=') {
+                        self.advance();
+                        Some(Token::new(
+                            TokenType::PLUS_EQUAL,
+                            "+=".to_string(),
+                            self.current_line,
+                        ))
+                    } else if self.match_next('+') {
+                        self.advance();
+                        Some(Token::new(
+                            TokenType::PLUS_PLUS,
+                            "++".to_string(),
+                            self.current_line,
+                        ))
+                    } else {
+                        Some(Token::new(
+                            TokenType::PLUS,
+                            c.to_string(),
+                            self.current_line,
+                        ))
+                    }
+                }
+                ';' => Some(Token::new(
+                    TokenType::SEMICOLON,
+                    c.to_string(),
+                    self.current_line,
+                )),
+                '-' => {
+                    if self.match_next('=') {
+                        self.advance();
+                        Some(Token::new(
+                            TokenType::MINUS_EQUAL,
+                            "-=".to_string(),
+                            self.current_line,
+                        ))
+                    } else if self.match_next('-') {
+                        self.advance();
+                        Some(Token::new(
+                            TokenType::MINUS_MINUS,
+                            "--".to_string(),
+                            self.current_line,
+                        ))
+                    } else {
+                        Some(Token::new(
+                            TokenType::MINUS,
+                            c.to_string(),
+                            self.current_line,
+                        ))
+                    }
+                }
                 '=' => {
                     if self.match_next('=') {
                         self.advance();
@@ -87,6 +255,13 @@ impl<'a> Lexer<'a> {
                             "==".to_string(),
                             self.current_line,
                         ))
+                    } else if self.match_next('>') {
+                        self.advance();
+                        Some(Token::new(
+                            TokenType::ARROW,
+                            "=>".to_string(),
+                            self.current_line,
+                        ))
                     } else {
                         Some(Token::new(
                             TokenType::EQUAL,
@@ -119,6 +294,13 @@ impl<'a> Lexer<'a> {
                             ">=".to_string(),
                             self.current_line,
                         ))
+                    } else if self.match_next('>') {
+                        self.advance();
+                        Some(Token::new(
+                            TokenType::GREATER_GREATER,
+                            ">>".to_string(),
+                            self.current_line,
+                        ))
                     } else {
                         Some(Token::new(
                             TokenType::GREATER,
@@ -135,6 +317,13 @@ impl<'a> Lexer<'a> {
                             "<=".to_string(),
                             self.current_line,
                         ))
+                    } else if self.match_next('<') {
+                        self.advance();
+                        Some(Token::new(
+                            TokenType::LESS_LESS,
+                            "<<".to_string(),
+                            self.current_line,
+                        ))
                     } else {
                         Some(Token::new(
                             TokenType::LESS,
@@ -146,13 +335,20 @@ impl<'a> Lexer<'a> {
 
                 '/' => {
                     if self.match_next('/') {
-                        while let Some(c) = self.current_char {
-                            if c == '\n' {
+                        while let Some(&next_char) = self.source.peek() {
+                            if next_char == '\n' {
                                 break;
                             }
                             self.advance();
                         }
                         None
+                    } else if self.match_next('=') {
+                        self.advance();
+                        Some(Token::new(
+                            TokenType::SLASH_EQUAL,
+                            "/=".to_string(),
+                            self.current_line,
+                        ))
                     } else {
                         Some(Token::new(
                             TokenType::SLASH,
@@ -162,26 +358,7 @@ impl<'a> Lexer<'a> {
                     }
                 }
 
-                '"' => {
-                    let mut value = String::new();
-                    let mut seen_end_quote: bool = false;
-                    self.advance();
-                    while let Some(c) = self.current_char {
-                        if c == '"' {
-                            seen_end_quote = true;
-                            break;
-                        }
-                        value.push(c);
-                        self.advance();
-                    }
-                    if seen_end_quote == false {
-                        report_error(self.current_line, "Unterminated string.");
-                        self.num_errors += 1;
-                        None
-                    } else {
-                        Some(Token::new(TokenType::STRING, value, self.current_line))
-                    }
-                }
+                '"' => self.string(start, start_line, start_column),
                 ' ' | '\r' | '\t' | '\n' => None,
                 _ => {
                     if c.is_ascii_digit() {
@@ -203,11 +380,17 @@ impl<'a> Lexer<'a> {
 
                         if value.ends_with(".") {
                             value.push_str("0");
-                            self.tokens.push(Token::new(
+                            let mut number = Token::new(
                                 TokenType::NUMBER,
                                 value,
                                 self.current_line,
-                            ));
+                            );
+                            number.span = Span::new(self.file, start, self.current_offset, start_line, start_column);
+                            self.tokens.push(number);
+                            // the trailing '.' becomes a token of its own
+                            start = self.current_offset;
+                            start_line = self.current_line;
+                            start_column = self.current_column;
                             Some(Token::new(
                                 TokenType::DOT,
                                 ".".to_string(),
@@ -228,28 +411,40 @@ impl<'a> Lexer<'a> {
                         }
                         match value.as_str() {
                             "and" => Some(Token::new(TokenType::AND, value, self.current_line)),
+                            "break" => Some(Token::new(TokenType::BREAK, value, self.current_line)),
+                            "catch" => Some(Token::new(TokenType::CATCH, value, self.current_line)),
                             "class" => Some(Token::new(TokenType::CLASS, value, self.current_line)),
+                            "continue" => {
+                                Some(Token::new(TokenType::CONTINUE, value, self.current_line))
+                            }
                             "else" => Some(Token::new(TokenType::ELSE, value, self.current_line)),
                             "false" => Some(Token::new(TokenType::FALSE, value, self.current_line)),
+                            "finally" => {
+                                Some(Token::new(TokenType::FINALLY, value, self.current_line))
+                            }
                             "for" => Some(Token::new(TokenType::FOR, value, self.current_line)),
                             "fn" => Some(Token::new(TokenType::FN, value, self.current_line)),
                             "if" => Some(Token::new(TokenType::IF, value, self.current_line)),
+                            "import" => Some(Token::new(TokenType::IMPORT, value, self.current_line)),
                             "null" => Some(Token::new(TokenType::NULL, value, self.current_line)),
                             "or" => Some(Token::new(TokenType::OR, value, self.current_line)),
                             "print" => Some(Token::new(TokenType::PRINT, value, self.current_line)),
                             "return" => {
                                 Some(Token::new(TokenType::RETURN, value, self.current_line))
                             }
-                            "crate" => Some(Token::new(TokenType::SUPER, value, self.current_line)),
+                            "super" => Some(Token::new(TokenType::SUPER, value, self.current_line)),
                             "this" => Some(Token::new(TokenType::THIS, value, self.current_line)),
+                            "throw" => Some(Token::new(TokenType::THROW, value, self.current_line)),
                             "true" => Some(Token::new(TokenType::TRUE, value, self.current_line)),
+                            "try" => Some(Token::new(TokenType::TRY, value, self.current_line)),
                             "var" => Some(Token::new(TokenType::VAR, value, self.current_line)),
                             "while" => Some(Token::new(TokenType::WHILE, value, self.current_line)),
                             _ => Some(Token::new(TokenType::IDENTIFIER, value, self.current_line)),
                         }
                     } else {
-                        report_error(
-                            self.current_line,
+                        let span = Span::new(self.file, start, self.current_offset + c.len_utf8(), start_line, start_column);
+                        self.report_error(
+                            span,
                             format!("Unexpected character: {}", c.to_string()).as_str(),
                         );
                         self.num_errors += 1;
@@ -258,21 +453,147 @@ impl<'a> Lexer<'a> {
                 }
             };
 
-            if let Some(token) = token {
+            if let Some(mut token) = token {
+                // the current character is the last one the token consumed
+                let end = self.current_offset + self.current_char.map_or(0, char::len_utf8);
+                token.span = Span::new(self.file, start, end, start_line, start_column);
                 self.tokens.push(token);
             }
 
             self.advance();
         }
 
-        self.tokens.push(Token::new(
+        let mut eof = Token::new(
             TokenType::EOF,
             "".to_string(),
             self.current_line,
-        ));
+        );
+        eof.span = Span::new(self.file, self.current_offset, self.current_offset, self.current_line, self.current_column);
+        self.tokens.push(eof);
         self.tokens.clone()
     }
 
+    // A string literal, `"..."` or `"""..."""`, either of which may span lines.
+    fn string(&mut self, start: usize, start_line: usize, start_column: usize) -> Option<Token> {
+        let delimiter = if self.text[start..].starts_with("\"\"\"") { "\"\"\"" } else { "\"" };
+        for _ in 0..delimiter.len() {
+            self.advance();
+        }
+        self.string_body(delimiter, start, start_line, start_column)
+    }
+
+    // The text of a string up to its closing quote, leaving that as the current
+    // character. A `${` on the way ends the text early instead, as an
+    // `INTERPOLATION` token; the expression inside is lexed as usual and its
+    // closing `}` comes back here for the rest of the string.
+    fn string_body(&mut self, delimiter: &'static str, start: usize, start_line: usize, start_column: usize) -> Option<Token> {
+        let mut value = String::new();
+        loop {
+            match self.current_char {
+                None => {
+                    let span = Span::new(self.file, start, self.current_offset, start_line, start_column);
+                    self.report_error(span, "Unterminated string.");
+                    self.num_errors += 1;
+                    self.unterminated = true;
+                    return None;
+                }
+                Some('"') if self.text[self.current_offset..].starts_with(delimiter) => break,
+                Some('$') if self.text[self.current_offset..].starts_with("${") => {
+                    self.advance();
+                    self.interpolations.push((delimiter, 0));
+                    let lexeme = self.text[start..=self.current_offset].to_string();
+                    return Some(Token::new(TokenType::INTERPOLATION, lexeme, start_line).with_literal(value));
+                }
+                Some('\\') => {
+                    if let Some(c) = self.escape() {
+                        value.push(c);
+                    }
+                }
+                Some(c) => {
+                    value.push(c);
+                    self.advance();
+                }
+            }
+        }
+        for _ in 1..delimiter.len() {
+            self.advance();
+        }
+
+        let lexeme = self.text[start..=self.current_offset].to_string();
+        Some(Token::new(TokenType::STRING, lexeme, start_line).with_literal(value))
+    }
+
+    // The character an escape sequence stands for, reading from its backslash up
+    // to the first character after it. Unknown or malformed escapes are reported
+    // and stand for nothing.
+    fn escape(&mut self) -> Option<char> {
+        let (start, line, column) = (self.current_offset, self.current_line, self.current_column);
+        self.advance();
+        let escaped = match self.current_char {
+            Some('n') => Some('\n'),
+            Some('t') => Some('\t'),
+            Some('r') => Some('\r'),
+            Some('0') => Some('\0'),
+            Some('"') => Some('"'),
+            Some('$') => Some('$'),
+            Some('\\') => Some('\\'),
+            Some('u') => return self.unicode_escape(start, line, column),
+            // left for the string's own loop, which reports it unterminated
+            None => return None,
+            Some(c) => {
+                let span = Span::new(self.file, start, self.current_offset + c.len_utf8(), line, column);
+                self.report_error(span, &format!("Unknown escape sequence: \\{}", c));
+                self.num_errors += 1;
+                None
+            }
+        };
+        self.advance();
+        escaped
+    }
+
+    // `\u{...}`: one to six hex digits naming a Unicode scalar value
+    fn unicode_escape(&mut self, start: usize, line: usize, column: usize) -> Option<char> {
+        self.advance();
+        let mut digits = String::new();
+        let closed = if self.current_char == Some('{') {
+            self.advance();
+            while let Some(c) = self.current_char.filter(char::is_ascii_hexdigit) {
+                digits.push(c);
+                self.advance();
+            }
+            self.current_char == Some('}')
+        } else {
+            false
+        };
+
+        let escaped = match u32::from_str_radix(&digits, 16) {
+            Ok(code) if closed && digits.len() <= 6 => char::from_u32(code),
+            _ => None,
+        };
+        if closed {
+            self.advance();
+        }
+        if escaped.is_none() {
+            let span = Span::new(self.file, start, self.current_offset, line, column);
+            self.report_error(span, "Invalid unicode escape; expected \\u{...} with 1 to 6 hex digits naming a character.");
+            self.num_errors += 1;
+        }
+        escaped
+    }
+
+    // printed straight away, rendered against this file alone
+    fn report_error(&self, span: Span, message: &str) {
+        if self.quiet {
+            return;
+        }
+        let mut sources = SourceMap::new();
+        sources.add(&self.name, self.text);
+        let span = Span { file: 0, ..span };
+        Diagnostic::new(format!("[line {}] Error: {}", span.line, message))
+            .with_span(span)
+            .emit(&sources);
+    }
+
     fn match_next(&mut self, expected: char) -> bool {
         if let Some(next) = self.source.peek() {
             if *next == expected {
@@ -283,10 +604,15 @@ impl<'a> Lexer<'a> {
     }
 
     fn advance(&mut self) -> () {
+        if let Some(c) = self.current_char {
+            self.current_offset += c.len_utf8();
+        }
         if self.current_char == Some('\n') {
             self.current_line += 1;
+            self.current_column = 1;
+        } else {
+            self.current_column += 1;
         }
-        self.current_column += 1;
         self.current_char = self.source.next();
     }
 }
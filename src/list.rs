@@ -0,0 +1,115 @@
+use crate::error::RuntimeError;
+use crate::native::{type_name, NativeFunction, Runtime};
+use crate::object::Object;
+use crate::token::Token;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+pub fn new_list(elements: Vec<Object>) -> Object {
+    Object::List {
+        elements: Rc::new(RefCell::new(elements)),
+    }
+}
+
+// Maps a script index onto a position in `0..len`; negative indices count from the end.
+pub fn normalize_index(index: &Object, len: usize, bracket: &Token) -> Result<usize, RuntimeError> {
+    let value = match index {
+        Object::Number { value } if value.fract() == 0.0 => *value,
+        other => {
+            return Err(RuntimeError::InvalidOperandType(
+                (*bracket).clone(),
+                format!("List indices must be integers, found {}", type_name(other)),
+            ))
+        }
+    };
+
+    let position = if value < 0.0 { value + len as f64 } else { value };
+    if position < 0.0 || position >= len as f64 {
+        return Err(RuntimeError::IndexOutOfRange(
+            (*bracket).clone(),
+            format!("Index {} is out of range for list of length {}", value, len),
+        ));
+    }
+    Ok(position as usize)
+}
+
+pub fn get(elements: &Rc<RefCell<Vec<Object>>>, index: &Object, bracket: &Token) -> Result<Object, RuntimeError> {
+    let elements = elements.borrow();
+    let position = normalize_index(index, elements.len(), bracket)?;
+    Ok(elements[position].clone())
+}
+
+pub fn set(elements: &Rc<RefCell<Vec<Object>>>, index: &Object, value: Object, bracket: &Token) -> Result<(), RuntimeError> {
+    let mut elements = elements.borrow_mut();
+    let position = normalize_index(index, elements.len(), bracket)?;
+    elements[position] = value;
+    Ok(())
+}
+
+// Built-in methods, looked up through `xs.name` and bound to the list.
+pub fn method(name: &str) -> Option<NativeFunction> {
+    match name {
+        "push" => Some(NativeFunction::new("push", 1, push)),
+        "pop" => Some(NativeFunction::new("pop", 0, pop)),
+        "slice" => Some(NativeFunction::new("slice", 2, slice)),
+        "len" => Some(NativeFunction::new("len", 0, len)),
+        _ => None,
+    }
+}
+
+fn receiver(args: &[Object]) -> Rc<RefCell<Vec<Object>>> {
+    match &args[0] {
+        Object::List { elements } => Rc::clone(elements),
+        _ => unreachable!("list methods are only bound to lists"),
+    }
+}
+
+fn push(_interp: &mut dyn Runtime, _paren: &Token, args: Vec<Object>) -> Result<Object, RuntimeError> {
+    receiver(&args).borrow_mut().push(args[1].clone());
+    Ok(Object::Null)
+}
+
+fn pop(_interp: &mut dyn Runtime, paren: &Token, args: Vec<Object>) -> Result<Object, RuntimeError> {
+    match receiver(&args).borrow_mut().pop() {
+        Some(value) => Ok(value),
+        None => Err(RuntimeError::IndexOutOfRange(
+            (*paren).clone(),
+            "Cannot pop from an empty list".to_string(),
+        )),
+    }
+}
+
+// slice(start, end) copies the half-open range; bounds are clamped like Python's
+fn slice(_interp: &mut dyn Runtime, paren: &Token, args: Vec<Object>) -> Result<Object, RuntimeError> {
+    let elements = receiver(&args);
+    let elements = elements.borrow();
+    let len = elements.len() as f64;
+
+    let mut bounds = [0usize; 2];
+    for (i, arg) in args[1..].iter().enumerate() {
+        let value = match arg {
+            Object::Number { value } if value.fract() == 0.0 => *value,
+            Object::Null if i == 1 => len,
+            other => {
+                return Err(RuntimeError::InvalidOperandType(
+                    (*paren).clone(),
+                    format!("Slice bounds must be integers, found {}", type_name(other)),
+                ))
+            }
+        };
+        let value = if value < 0.0 { value + len } else { value };
+        bounds[i] = value.max(0.0).min(len) as usize;
+    }
+
+    let [start, end] = bounds;
+    if start >= end {
+        return Ok(new_list(Vec::new()));
+    }
+    Ok(new_list(elements[start..end].to_vec()))
+}
+
+fn len(_interp: &mut dyn Runtime, _paren: &Token, args: Vec<Object>) -> Result<Object, RuntimeError> {
+    Ok(Object::Number {
+        value: receiver(&args).borrow().len() as f64,
+    })
+}
@@ -1,26 +1,98 @@
 #![ allow(warnings)]
 
 use ast::ast::AstPrinter;
+use ast::stmt::Stmt;
+use diagnostic::{Diagnostic, SourceMap};
+use error::ParserError;
 use lexer::Lexer;
 use parser::Parser;
+use resolver::Resolver;
 use std::env;
 use std::fs;
 use std::io::{self, Write};
 use std::process::exit;
-use token::TokenType;
+use std::thread;
+use token::{Token, TokenType};
 mod scope;
 mod ast;
+mod class;
+mod diagnostic;
 mod interpreter;
 mod error;
 mod lexer;
+mod list;
+mod map;
+mod module;
 mod function;
+mod native;
 mod parser;
+mod repl;
+mod resolver;
 mod token;
 mod object;
+mod optimizer;
+mod ops;
+mod vm;
+
+// The tree-walker recurses on the Rust stack, so it runs on a thread with room for
+// `Interpreter`'s call-depth limit; overflowing that limit is a catchable error instead.
+const STACK_SIZE: usize = 256 * 1024 * 1024;
+// enough for one nested call in a debug build, which needs the most
+const STACK_PER_CALL: usize = 64 * 1024;
+
 fn main() {
+    let max_call_depth = max_call_depth();
+    // the VM keeps its frames on the heap; only the tree-walker's calls use this stack
+    let stack_size = if env::args().nth(1).as_deref() == Some("run") {
+        STACK_SIZE
+    } else {
+        STACK_SIZE.max(max_call_depth.saturating_mul(STACK_PER_CALL))
+    };
+    let worker = match thread::Builder::new()
+        .stack_size(stack_size)
+        .spawn(move || run(max_call_depth))
+    {
+        Ok(worker) => worker,
+        Err(e) => {
+            writeln!(io::stderr(), "Failed to start interpreter thread for a call depth of {}: {}", max_call_depth, e).unwrap();
+            exit(71);
+        }
+    };
+    if worker.join().is_err() {
+        exit(101);
+    }
+}
+
+// `SALMON_MAX_DEPTH` overrides how deeply calls may nest before raising `StackOverflow`
+fn max_call_depth() -> usize {
+    let value = match env::var("SALMON_MAX_DEPTH") {
+        Ok(value) => value,
+        Err(_) => return interpreter::DEFAULT_MAX_CALL_DEPTH,
+    };
+    match value.trim().parse::<usize>() {
+        Ok(depth) if depth > 0 => depth,
+        _ => {
+            writeln!(io::stderr(), "SALMON_MAX_DEPTH must be a positive integer, got {:?}", value).unwrap();
+            exit(64);
+        }
+    }
+}
+
+fn run(max_call_depth: usize) {
     let args: Vec<String> = env::args().collect();
+    if args.len() == 2 && args[1] == "repl" {
+        let mut repl = repl::Repl::new();
+        repl.set_max_call_depth(max_call_depth);
+        repl.run();
+        return;
+    }
     if args.len() < 3 {
-        writeln!(io::stderr(), "Usage: {} tokenize <filename>", args[0]).unwrap();
+        writeln!(
+            io::stderr(),
+            "Usage: {} <tokenize|parse|optimize|interp|run|interp-expr> <filename>\n       {} repl",
+            args[0], args[0]
+        )
+        .unwrap();
         return;
     }
 
@@ -34,13 +106,13 @@ fn main() {
     match command.as_str() {
         "tokenize" => {
             if !file_contents.is_empty() {
-                let mut lexer: Lexer = Lexer::new(file_contents.as_str());
+                let mut lexer: Lexer = Lexer::with_file(file_contents.as_str(), 0, filename);
                 lexer.tokenize();
                 lexer.get_tokens().into_iter().for_each(|token| {
-                    if token.token_type == TokenType::STRING {
+                    if token.token_type == TokenType::STRING || token.token_type == TokenType::INTERPOLATION {
                         println!(
-                            "{:?} \"{}\" {}",
-                            token.token_type, token.lexeme, token.lexeme
+                            "{:?} {} {}",
+                            token.token_type, token.lexeme, token.value()
                         );
                         return;
                     } else if token.token_type == TokenType::NUMBER {
@@ -77,71 +149,78 @@ fn main() {
         }
         "interp" => {
             if !file_contents.is_empty() {
-                let mut lexer: Lexer = Lexer::new(file_contents.as_str());
-                lexer.tokenize();
-
-                let mut parser : Parser = Parser::new(lexer.get_tokens());
                 let mut interpreter = interpreter::Interpreter::new();
-                match parser.parse() {
-                    Ok(stmts) => { interpreter.interpret(&stmts); }
-                    Err(e) => {
-                        writeln!(io::stderr(), "{}", e).unwrap();
-                    }
+                interpreter.set_max_call_depth(max_call_depth);
+                interpreter.imports.set_script_path(std::path::Path::new(filename));
+                let file = interpreter.sources.add(filename, &file_contents);
+                let stmts = parse_or_exit(&file_contents, file, filename, &interpreter.sources);
+                if let Err(errors) = Resolver::new(&mut interpreter).resolve(&stmts) {
+                    report_and_exit(&errors, &interpreter.sources);
                 }
-                
-                if parser.error_count > 0 {
-                    exit(65);
+                let stmts = optimizer::optimize(stmts);
+                if let Err(e) = interpreter.interpret(&stmts) {
+                    Diagnostic::from(&e).emit(&interpreter.sources);
+                    exit(70);
                 }
             }
         }
-        
-        "interp-expr" => {
+
+        // same as `interp`, but compiled to bytecode and run on the VM
+        "run" => {
             if !file_contents.is_empty() {
-                let mut lexer: Lexer = Lexer::new(file_contents.as_str());
-                lexer.tokenize();
+                let mut vm = vm::Vm::new();
+                vm.set_max_call_depth(max_call_depth);
+                vm.imports.set_script_path(std::path::Path::new(filename));
+                let file = vm.sources.add(filename, &file_contents);
+                let stmts = parse_or_exit(&file_contents, file, filename, &vm.sources);
+                if let Err(errors) = Resolver::standalone().resolve(&stmts) {
+                    report_and_exit(&errors, &vm.sources);
+                }
+                let stmts = optimizer::optimize(stmts);
+                if let Err(e) = vm.interpret(&stmts) {
+                    Diagnostic::from(&e).emit(&vm.sources);
+                    exit(70);
+                }
+            }
+        }
 
-                let mut parser: Parser = Parser::new(lexer.get_tokens());
+        "interp-expr" => {
+            if !file_contents.is_empty() {
                 let mut interpreter = interpreter::Interpreter::new();
-                match parser.parse_expr() {
+                let file = interpreter.sources.add(filename, &file_contents);
+                let tokens = lex_or_exit(&file_contents, file, filename);
+                match Parser::new(tokens).parse_expr() {
                     Ok(expr) => {
                         match interpreter.evaluate(&expr) {
                             Ok(obj) => {
                                 println!("{}", obj);
                             }
                             Err(e) => {
-                                writeln!(io::stderr(), "{}", e).unwrap();
+                                Diagnostic::from(&e).emit(&interpreter.sources);
                             }
                         }
                     }
-                    Err(e) => {
-                        writeln!(io::stderr(), "{}", e).unwrap();
-                    }
-                }
-                
-                if parser.error_count > 0 {
-                    exit(65);
+                    Err(e) => report_and_exit(&[e], &interpreter.sources),
                 }
             }
         }
         
         "parse" => {
             if !file_contents.is_empty() {
-                let mut lexer: Lexer = Lexer::new(file_contents.as_str());
-                lexer.tokenize();
+                let mut sources = SourceMap::new();
+                let file = sources.add(filename, &file_contents);
+                let ast = parse_or_exit(&file_contents, file, filename, &sources);
+                println!("{}", AstPrinter::print_stmt(ast));
+            }
+        }
 
-                let mut parser: Parser = Parser::new(lexer.get_tokens());
-                let ast = parser.parse();
-                match ast {
-                    Ok(ast) => {
-                        println!("{}", AstPrinter::print_stmt(ast));
-                    }
-                    Err(e) => {
-                        writeln!(io::stderr(), "{}", e).unwrap();
-                    }
-                }
-                if parser.error_count > 0 {
-                    exit(65);
-                }
+        // the tree as `interp` and `run` see it, after constant folding
+        "optimize" => {
+            if !file_contents.is_empty() {
+                let mut sources = SourceMap::new();
+                let file = sources.add(filename, &file_contents);
+                let ast = parse_or_exit(&file_contents, file, filename, &sources);
+                println!("{}", AstPrinter::print_stmt(optimizer::optimize(ast)));
             }
         }
         
@@ -152,3 +231,29 @@ fn main() {
         }
     }
 }
+
+// Lexes and parses a script, or reports what was rejected and exits.
+fn parse_or_exit(source: &str, file: usize, filename: &str, sources: &SourceMap) -> Vec<Stmt> {
+    let tokens = lex_or_exit(source, file, filename);
+    match Parser::new(tokens).parse() {
+        Ok(stmts) => stmts,
+        Err(errors) => report_and_exit(&errors, sources),
+    }
+}
+
+fn lex_or_exit(source: &str, file: usize, filename: &str) -> Vec<Token> {
+    let mut lexer: Lexer = Lexer::with_file(source, file, filename);
+    lexer.tokenize();
+    // the lexer has already printed what it rejected
+    if lexer.num_errors > 0 {
+        exit(65);
+    }
+    lexer.get_tokens()
+}
+
+fn report_and_exit(errors: &[ParserError], sources: &SourceMap) -> ! {
+    for e in errors {
+        Diagnostic::from(e).emit(sources);
+    }
+    exit(65);
+}
@@ -0,0 +1,146 @@
+use crate::error::RuntimeError;
+use crate::list::new_list;
+use crate::native::{type_name, NativeFunction, Runtime};
+use crate::object::Object;
+use crate::token::Token;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+// Insertion-ordered dictionary so that printing and key iteration are deterministic.
+#[derive(Debug, Clone, Default)]
+pub struct Map {
+    entries: Vec<(Object, Object)>,
+    index: HashMap<Object, usize>,
+}
+
+impl Map {
+    pub fn new() -> Map {
+        Map::default()
+    }
+
+    pub fn get(&self, key: &Object) -> Option<&Object> {
+        self.index.get(key).map(|&i| &self.entries[i].1)
+    }
+
+    pub fn insert(&mut self, key: Object, value: Object) {
+        match self.index.get(&key) {
+            Some(&i) => self.entries[i].1 = value,
+            None => {
+                self.index.insert(key.clone(), self.entries.len());
+                self.entries.push((key, value));
+            }
+        }
+    }
+
+    pub fn remove(&mut self, key: &Object) -> Option<Object> {
+        let position = self.index.remove(key)?;
+        let (_, value) = self.entries.remove(position);
+        for (_, i) in self.index.iter_mut() {
+            if *i > position {
+                *i -= 1;
+            }
+        }
+        Some(value)
+    }
+
+    pub fn contains(&self, key: &Object) -> bool {
+        self.index.contains_key(key)
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &(Object, Object)> {
+        self.entries.iter()
+    }
+}
+
+pub fn new_map(map: Map) -> Object {
+    Object::Map {
+        entries: Rc::new(RefCell::new(map)),
+    }
+}
+
+pub fn check_key(key: &Object, token: &Token) -> Result<(), RuntimeError> {
+    if key.is_hashable() {
+        Ok(())
+    } else {
+        Err(RuntimeError::InvalidOperandType(
+            (*token).clone(),
+            format!("Map keys must be strings, numbers, booleans or null, found {}", type_name(key)),
+        ))
+    }
+}
+
+pub fn get(entries: &Rc<RefCell<Map>>, key: &Object, bracket: &Token) -> Result<Object, RuntimeError> {
+    check_key(key, bracket)?;
+    match entries.borrow().get(key) {
+        Some(value) => Ok(value.clone()),
+        None => Err(RuntimeError::KeyNotFound(
+            (*bracket).clone(),
+            format!("Key {} is not in the map", key),
+        )),
+    }
+}
+
+pub fn set(entries: &Rc<RefCell<Map>>, key: Object, value: Object, bracket: &Token) -> Result<(), RuntimeError> {
+    check_key(&key, bracket)?;
+    entries.borrow_mut().insert(key, value);
+    Ok(())
+}
+
+// Built-in methods, looked up through `m.name` and bound to the map.
+pub fn method(name: &str) -> Option<NativeFunction> {
+    match name {
+        "has" => Some(NativeFunction::new("has", 1, has)),
+        "keys" => Some(NativeFunction::new("keys", 0, keys)),
+        "values" => Some(NativeFunction::new("values", 0, values)),
+        "remove" => Some(NativeFunction::new("remove", 1, remove)),
+        "len" => Some(NativeFunction::new("len", 0, len)),
+        _ => None,
+    }
+}
+
+fn receiver(args: &[Object]) -> Rc<RefCell<Map>> {
+    match &args[0] {
+        Object::Map { entries } => Rc::clone(entries),
+        _ => unreachable!("map methods are only bound to maps"),
+    }
+}
+
+fn has(_interp: &mut dyn Runtime, paren: &Token, args: Vec<Object>) -> Result<Object, RuntimeError> {
+    check_key(&args[1], paren)?;
+    Ok(Object::Boolean {
+        value: receiver(&args).borrow().contains(&args[1]),
+    })
+}
+
+fn keys(_interp: &mut dyn Runtime, _paren: &Token, args: Vec<Object>) -> Result<Object, RuntimeError> {
+    let keys = receiver(&args).borrow().iter().map(|(key, _)| key.clone()).collect();
+    Ok(new_list(keys))
+}
+
+fn values(_interp: &mut dyn Runtime, _paren: &Token, args: Vec<Object>) -> Result<Object, RuntimeError> {
+    let values = receiver(&args).borrow().iter().map(|(_, value)| value.clone()).collect();
+    Ok(new_list(values))
+}
+
+// removes the key and hands back its value
+fn remove(_interp: &mut dyn Runtime, paren: &Token, args: Vec<Object>) -> Result<Object, RuntimeError> {
+    check_key(&args[1], paren)?;
+    match receiver(&args).borrow_mut().remove(&args[1]) {
+        Some(value) => Ok(value),
+        None => Err(RuntimeError::KeyNotFound(
+            (*paren).clone(),
+            format!("Key {} is not in the map", args[1]),
+        )),
+    }
+}
+
+fn len(_interp: &mut dyn Runtime, _paren: &Token, args: Vec<Object>) -> Result<Object, RuntimeError> {
+    Ok(Object::Number {
+        value: receiver(&args).borrow().len() as f64,
+    })
+}
@@ -0,0 +1,152 @@
+use crate::ast::stmt::Stmt;
+use crate::diagnostic::{Diagnostic, SourceMap};
+use crate::error::{ParserError, RuntimeError};
+use crate::lexer::Lexer;
+use crate::parser::Parser;
+use crate::scope::Scope;
+use crate::token::Token;
+use crate::object::Object;
+use std::cell::RefCell;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+pub const EXTENSION: &str = "salmon";
+
+// The namespace value an `import` binds: the top-level scope the imported file ran in.
+#[derive(Debug)]
+pub struct Module {
+    pub name: String,
+    pub path: PathBuf,
+    scope: Rc<RefCell<Scope>>,
+}
+
+impl Module {
+    pub fn new(name: String, path: PathBuf, scope: Rc<RefCell<Scope>>) -> Module {
+        Module { name, path, scope }
+    }
+
+    pub fn get(&self, name: &Token) -> Result<Object, RuntimeError> {
+        match self.scope.borrow().get_at(0, name) {
+            Ok(value) => Ok(value),
+            Err(_) => Err(RuntimeError::UndefinedProperty((*name).clone())),
+        }
+    }
+}
+
+impl fmt::Display for Module {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "<module {}>", self.name)
+    }
+}
+
+// Where the running backend resolves imports from: the directory of the file
+// running now, and the chain of files being imported, outermost first.
+#[derive(Debug)]
+pub struct Imports {
+    pub current_dir: PathBuf,
+    pub importing: Vec<PathBuf>,
+}
+
+impl Imports {
+    pub fn new() -> Imports {
+        Imports {
+            current_dir: PathBuf::from("."),
+            importing: Vec::new(),
+        }
+    }
+
+    // Records the entry script so relative imports and import cycles back to it work.
+    pub fn set_script_path(&mut self, path: &Path) {
+        if let Some(dir) = path.parent() {
+            self.current_dir = dir.to_path_buf();
+        }
+        if let Ok(canonical) = fs::canonicalize(path) {
+            self.importing = vec![canonical];
+        }
+    }
+
+    // moves into the module at `canonical`, returning the directory to go back to
+    pub fn enter(&mut self, canonical: &Path) -> PathBuf {
+        let module_dir = canonical.parent().map(Path::to_path_buf).unwrap_or_default();
+        self.importing.push(canonical.to_path_buf());
+        std::mem::replace(&mut self.current_dir, module_dir)
+    }
+
+    pub fn leave(&mut self, previous_dir: PathBuf) {
+        self.importing.pop();
+        self.current_dir = previous_dir;
+    }
+}
+
+// `import "lib/util"` means `lib/util.salmon` next to the importing file
+pub fn resolve_path(dir: &Path, path: &str) -> PathBuf {
+    let mut target = dir.join(path);
+    if target.extension().is_none() {
+        target.set_extension(EXTENSION);
+    }
+    target
+}
+
+// the name an import binds when no `as` clause is given
+pub fn default_name(path: &str) -> Option<String> {
+    let stem = Path::new(path).file_stem()?.to_str()?;
+    let mut chars = stem.chars();
+    let first = chars.next()?;
+    if (first.is_alphabetic() || first == '_') && chars.all(|c| c.is_alphanumeric() || c == '_') {
+        Some(stem.to_string())
+    } else {
+        None
+    }
+}
+
+// The steps of an import both backends share; each returns the message for an `ImportError`.
+
+// the canonical path of the file `import "path"` refers to
+pub fn locate(dir: &Path, path: &str) -> Result<PathBuf, String> {
+    let target = resolve_path(dir, path);
+    fs::canonicalize(&target)
+        .map_err(|e| format!("Cannot find module \"{}\" ({}): {}", path, target.display(), e))
+}
+
+// `importing` is the chain of files being imported, outermost first
+pub fn check_cycle(importing: &[PathBuf], canonical: &PathBuf) -> Result<(), String> {
+    match importing.iter().position(|p| p == canonical) {
+        Some(start) => {
+            let cycle: Vec<String> = importing[start..]
+                .iter()
+                .chain(std::iter::once(canonical))
+                .map(|p| p.display().to_string())
+                .collect();
+            Err(format!("Import cycle detected: {}", cycle.join(" -> ")))
+        }
+        None => Ok(()),
+    }
+}
+
+// reads and parses the module, registering its source so later errors can show it
+pub fn parse(canonical: &Path, path: &str, sources: &mut SourceMap) -> Result<Vec<Stmt>, String> {
+    let source = fs::read_to_string(canonical)
+        .map_err(|e| format!("Cannot read module \"{}\": {}", path, e))?;
+
+    let name = canonical.display().to_string();
+    let file = sources.add(&name, &source);
+    let mut lexer = Lexer::with_file(source.as_str(), file, &name);
+    lexer.tokenize();
+    if lexer.num_errors > 0 {
+        return Err(format!("Module \"{}\" failed to tokenize", path));
+    }
+    let mut parser = Parser::new(lexer.get_tokens());
+    parser.parse().map_err(|errors| {
+        for e in &errors {
+            Diagnostic::from(e).emit(sources);
+        }
+        format!("Module \"{}\" failed to parse", path)
+    })
+}
+
+pub fn resolve_failure(path: &str, errors: &[ParserError]) -> String {
+    let messages: Vec<String> = errors.iter().map(|e| e.to_string()).collect();
+    format!("Module \"{}\" failed to resolve: {}", path, messages.join("; "))
+}
@@ -0,0 +1,196 @@
+use crate::error::RuntimeError;
+use crate::interpreter::Frame;
+use crate::list::new_list;
+use crate::object::Object;
+use crate::token::Token;
+use std::fmt;
+use std::io::{self, BufRead, Write};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// What a native can ask of whichever backend is running it.
+pub trait Runtime {
+    // the calls in progress, outermost first
+    fn call_stack(&self) -> &[Frame];
+}
+
+// `paren` is the call-site token, used to locate any error the native raises
+pub type NativeFn = fn(&mut dyn Runtime, &Token, Vec<Object>) -> Result<Object, RuntimeError>;
+
+#[derive(Debug, Clone)]
+pub struct NativeFunction {
+    pub name: String,
+    arity: usize,
+    min_arity: usize,
+    func: NativeFn,
+    receiver: Option<Box<Object>>,
+}
+
+impl NativeFunction {
+    pub fn new(name: &str, arity: usize, func: NativeFn) -> NativeFunction {
+        NativeFunction {
+            name: name.to_string(),
+            arity,
+            min_arity: arity,
+            func,
+            receiver: None,
+        }
+    }
+
+    // the last `count` parameters may be left out of a call
+    pub fn optional(mut self, count: usize) -> NativeFunction {
+        self.min_arity = self.arity - count;
+        self
+    }
+
+    pub fn call(&self, interp: &mut dyn Runtime, paren: &Token, mut args: Vec<Object>) -> Result<Object, RuntimeError> {
+        if let Some(receiver) = &self.receiver {
+            args.insert(0, (**receiver).clone());
+        }
+        (self.func)(interp, paren, args)
+    }
+
+    // a bound native receives its receiver as the first argument
+    pub fn bind(mut self, receiver: Object) -> NativeFunction {
+        self.receiver = Some(Box::new(receiver));
+        self
+    }
+
+    pub fn arity(&self) -> usize {
+        self.arity
+    }
+
+    pub fn min_arity(&self) -> usize {
+        self.min_arity
+    }
+}
+
+impl fmt::Display for NativeFunction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "<native fn {}>", self.name)
+    }
+}
+
+pub fn prelude() -> Vec<NativeFunction> {
+    vec![
+        NativeFunction::new("clock", 0, clock),
+        NativeFunction::new("len", 1, len),
+        NativeFunction::new("str", 1, str),
+        NativeFunction::new("num", 1, num),
+        NativeFunction::new("type", 1, type_of),
+        NativeFunction::new("input", 1, input).optional(1),
+        NativeFunction::new("assert", 2, assert),
+        NativeFunction::new("callstack", 0, callstack),
+    ]
+}
+
+fn clock(_interp: &mut dyn Runtime, _paren: &Token, _args: Vec<Object>) -> Result<Object, RuntimeError> {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs_f64())
+        .unwrap_or(0.0);
+    Ok(Object::Number { value: now })
+}
+
+fn len(_interp: &mut dyn Runtime, paren: &Token, args: Vec<Object>) -> Result<Object, RuntimeError> {
+    match &args[0] {
+        Object::String { value } => Ok(Object::Number {
+            value: value.chars().count() as f64,
+        }),
+        Object::List { elements } => Ok(Object::Number {
+            value: elements.borrow().len() as f64,
+        }),
+        Object::Map { entries } => Ok(Object::Number {
+            value: entries.borrow().len() as f64,
+        }),
+        other => Err(RuntimeError::InvalidOperandType(
+            (*paren).clone(),
+            format!("len() is not defined for {}", type_name(other)),
+        )),
+    }
+}
+
+fn str(_interp: &mut dyn Runtime, _paren: &Token, args: Vec<Object>) -> Result<Object, RuntimeError> {
+    Ok(Object::String {
+        value: args[0].to_string(),
+    })
+}
+
+fn num(_interp: &mut dyn Runtime, paren: &Token, args: Vec<Object>) -> Result<Object, RuntimeError> {
+    match &args[0] {
+        Object::Number { value } => Ok(Object::Number { value: *value }),
+        Object::String { value } => match value.trim().parse::<f64>() {
+            Ok(value) => Ok(Object::Number { value }),
+            Err(_) => Err(RuntimeError::InvalidOperandType(
+                (*paren).clone(),
+                format!("Cannot convert \"{}\" to a number", value),
+            )),
+        },
+        other => Err(RuntimeError::InvalidOperandType(
+            (*paren).clone(),
+            format!("Cannot convert {} to a number", type_name(other)),
+        )),
+    }
+}
+
+fn type_of(_interp: &mut dyn Runtime, _paren: &Token, args: Vec<Object>) -> Result<Object, RuntimeError> {
+    Ok(Object::String {
+        value: type_name(&args[0]).to_string(),
+    })
+}
+
+fn input(_interp: &mut dyn Runtime, paren: &Token, args: Vec<Object>) -> Result<Object, RuntimeError> {
+    if let Some(prompt) = args.first() {
+        print!("{}", prompt);
+        io::stdout().flush().ok();
+    }
+
+    let mut line = String::new();
+    if io::stdin().lock().read_line(&mut line).is_err() {
+        return Err(RuntimeError::InvalidFunctionCall(
+            (*paren).clone(),
+            "Failed to read from standard input".to_string(),
+        ));
+    }
+    if line.ends_with('\n') {
+        line.pop();
+        if line.ends_with('\r') {
+            line.pop();
+        }
+    }
+    Ok(Object::String { value: line })
+}
+
+fn assert(_interp: &mut dyn Runtime, paren: &Token, args: Vec<Object>) -> Result<Object, RuntimeError> {
+    if args[0].is_truthy() {
+        Ok(Object::Null)
+    } else {
+        Err(RuntimeError::AssertionFailed((*paren).clone(), args[1].to_string()))
+    }
+}
+
+// the calls leading here, outermost first, as "name (line N)"; excludes `callstack` itself
+fn callstack(interp: &mut dyn Runtime, _paren: &Token, _args: Vec<Object>) -> Result<Object, RuntimeError> {
+    let stack = interp.call_stack();
+    let frames = stack[..stack.len().saturating_sub(1)]
+        .iter()
+        .map(|frame| Object::String {
+            value: format!("{} (line {})", frame.function, frame.call_site.line),
+        })
+        .collect();
+    Ok(new_list(frames))
+}
+
+pub fn type_name(object: &Object) -> &'static str {
+    match object {
+        Object::Boolean { .. } => "boolean",
+        Object::Null => "null",
+        Object::Number { .. } => "number",
+        Object::String { .. } => "string",
+        Object::Callable { .. } | Object::Native { .. } | Object::Closure { .. } | Object::BoundMethod { .. } => "function",
+        Object::Class { .. } => "class",
+        Object::Instance { .. } => "instance",
+        Object::List { .. } => "list",
+        Object::Map { .. } => "map",
+        Object::Module { .. } => "module",
+    }
+}
@@ -1,6 +1,15 @@
 use std::fmt::Display;
+use crate::class::{Class, Instance};
 use crate::function::Function;
+use crate::map::Map;
+use crate::module::Module;
+use crate::native::NativeFunction;
+use crate::vm::Closure;
+use std::cell::RefCell;
 use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::mem;
+use std::rc::Rc;
 
 #[derive(Debug, Clone)]
 pub enum Object {
@@ -17,29 +26,189 @@ pub enum Object {
     
     Callable {
         func : Function
-    }
+    },
+
+    Native {
+        func: NativeFunction,
+    },
+
+    // functions compiled for the VM, and VM methods looked up on an instance
+    Closure {
+        closure: Rc<Closure>,
+    },
+
+    BoundMethod {
+        receiver: Box<Object>,
+        method: Rc<Closure>,
+    },
+
+    Class {
+        class: Rc<Class>,
+    },
+
+    Instance {
+        instance: Rc<RefCell<Instance>>,
+    },
+
+    List {
+        elements: Rc<RefCell<Vec<Object>>>,
+    },
+
+    Map {
+        entries: Rc<RefCell<Map>>,
+    },
+
+    Module {
+        module: Rc<Module>,
+    },
 }
 
-impl Display for Object {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+impl Object {
+    // only `false` and `null` are falsey
+    pub fn is_truthy(&self) -> bool {
+        match self {
+            Object::Boolean { value } => *value,
+            Object::Null => false,
+            _ => true,
+        }
+    }
+
+    // only immutable values may be used as map keys
+    pub fn is_hashable(&self) -> bool {
+        matches!(
+            self,
+            Object::Boolean { .. } | Object::Null | Object::Number { .. } | Object::String { .. }
+        )
+    }
+
+    // strings are quoted when they appear inside a collection
+    fn fmt_nested(&self, f: &mut fmt::Formatter, seen: &mut Vec<*const ()>) -> fmt::Result {
+        match self {
+            Object::String { value } => write!(f, "\"{}\"", value),
+            _ => self.fmt_seen(f, seen),
+        }
+    }
+
+    // `seen` holds the collections being printed around this value; running into one
+    // of them again prints `[...]` or `{...}` instead of recursing forever
+    fn fmt_seen(&self, f: &mut fmt::Formatter, seen: &mut Vec<*const ()>) -> fmt::Result {
         match self {
             Object::Boolean { value } => write!(f, "{}", value),
             Object::Null => write!(f, "null"),
             Object::Number { value } => write!(f, "{}", value),
             Object::String { value } => write!(f, "{}", value),
             Object::Callable { func } => write!(f, "{}", func),
+            Object::Native { func } => write!(f, "{}", func),
+            Object::Closure { closure } => write!(f, "{}", closure),
+            Object::BoundMethod { method, .. } => write!(f, "{}", method),
+            Object::Class { class } => write!(f, "{}", class),
+            Object::Instance { instance } => write!(f, "{}", instance.borrow()),
+            Object::List { elements } => {
+                let id = Rc::as_ptr(elements) as *const ();
+                if seen.contains(&id) {
+                    return write!(f, "[...]");
+                }
+                seen.push(id);
+                write!(f, "[")?;
+                for (i, element) in elements.borrow().iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    element.fmt_nested(f, seen)?;
+                }
+                seen.pop();
+                write!(f, "]")
+            }
+            Object::Map { entries } => {
+                let id = Rc::as_ptr(entries) as *const ();
+                if seen.contains(&id) {
+                    return write!(f, "{{...}}");
+                }
+                seen.push(id);
+                write!(f, "{{")?;
+                for (i, (key, value)) in entries.borrow().iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    key.fmt_nested(f, seen)?;
+                    write!(f, ": ")?;
+                    value.fmt_nested(f, seen)?;
+                }
+                seen.pop();
+                write!(f, "}}")
+            }
+            Object::Module { module } => write!(f, "{}", module),
         }
     }
-}
 
-impl PartialEq for Object {
-    fn eq(&self, other: &Self) -> bool {
+    // `comparing` holds the pairs of collections being compared around these values;
+    // running into a pair again counts as equal, so cyclic values compare in finite time
+    fn equals(&self, other: &Object, comparing: &mut Vec<(*const (), *const ())>) -> bool {
         match (self, other) {
             (Object::Boolean { value: a }, Object::Boolean { value: b }) => a == b,
             (Object::Null, Object::Null) => true,
             (Object::Number { value: a }, Object::Number { value: b }) => a == b,
             (Object::String { value: a }, Object::String { value: b }) => a == b,
+            (Object::Class { class: a }, Object::Class { class: b }) => Rc::ptr_eq(a, b),
+            (Object::Instance { instance: a }, Object::Instance { instance: b }) => Rc::ptr_eq(a, b),
+            (Object::List { elements: a }, Object::List { elements: b }) => {
+                let pair = (Rc::as_ptr(a) as *const (), Rc::as_ptr(b) as *const ());
+                if Rc::ptr_eq(a, b) || comparing.contains(&pair) {
+                    return true;
+                }
+                comparing.push(pair);
+                let (a, b) = (a.borrow(), b.borrow());
+                let equal = a.len() == b.len() && a.iter().zip(b.iter()).all(|(x, y)| x.equals(y, comparing));
+                comparing.pop();
+                equal
+            }
+            (Object::Map { entries: a }, Object::Map { entries: b }) => {
+                let pair = (Rc::as_ptr(a) as *const (), Rc::as_ptr(b) as *const ());
+                if Rc::ptr_eq(a, b) || comparing.contains(&pair) {
+                    return true;
+                }
+                comparing.push(pair);
+                let (a, b) = (a.borrow(), b.borrow());
+                let equal = a.len() == b.len()
+                    && a.iter().all(|(key, x)| b.get(key).is_some_and(|y| x.equals(y, comparing)));
+                comparing.pop();
+                equal
+            }
+            (Object::Module { module: a }, Object::Module { module: b }) => Rc::ptr_eq(a, b),
             _ => false,
         }
     }
-}
\ No newline at end of file
+}
+
+impl Display for Object {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.fmt_seen(f, &mut Vec::new())
+    }
+}
+
+impl PartialEq for Object {
+    fn eq(&self, other: &Self) -> bool {
+        self.equals(other, &mut Vec::new())
+    }
+}
+
+// Maps only accept hashable keys, and for those `eq` is a plain value comparison,
+// so `Eq` holds everywhere it matters. NaN is the one exception; it is never equal
+// to itself and therefore can be inserted but never found again.
+impl Eq for Object {}
+
+impl Hash for Object {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        mem::discriminant(self).hash(state);
+        match self {
+            Object::Boolean { value } => value.hash(state),
+            Object::Number { value } => {
+                // 0.0 and -0.0 compare equal so they must hash the same
+                let value = if *value == 0.0 { 0.0 } else { *value };
+                value.to_bits().hash(state);
+            }
+            Object::String { value } => value.hash(state),
+            _ => {}
+        }
+    }
+}
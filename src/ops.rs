@@ -0,0 +1,264 @@
+use crate::error::RuntimeError;
+use crate::class::Instance;
+use crate::list;
+use crate::map;
+use crate::native::type_name;
+use crate::object::Object;
+use crate::token::{Token, TokenType};
+
+// Operations on values shared by the tree-walker and the VM, so both backends
+// produce the same results and the same errors.
+
+pub fn literal(value: &Token) -> Result<Object, RuntimeError> {
+    match value.token_type {
+        TokenType::NUMBER => Ok(Object::Number {
+            value: value.lexeme.parse::<f64>().unwrap(),
+        }),
+        TokenType::STRING => Ok(Object::String {
+            value: value.value().to_string(),
+        }),
+        TokenType::TRUE => Ok(Object::Boolean { value: true }),
+        TokenType::FALSE => Ok(Object::Boolean { value: false }),
+        TokenType::NULL => Ok(Object::Null),
+        _ => Err(RuntimeError::InvalidLiteral((*value).clone(), "".to_string())),
+    }
+}
+
+pub fn unary(op: &Token, right: Object) -> Result<Object, RuntimeError> {
+    match op.token_type {
+        TokenType::MINUS => match right {
+            Object::Number { value } => Ok(Object::Number { value: -value }),
+            _ => Err(RuntimeError::InvalidUnaryOperation(
+                (*op).clone(),
+                "Operator can only be used on numbers".to_string(),
+            )),
+        },
+
+        TokenType::BANG => Ok(Object::Boolean {
+            value: !right.is_truthy(),
+        }),
+
+        TokenType::TILDE => match integer(&right) {
+            Some(value) => Ok(Object::Number { value: !value as f64 }),
+            None => Err(RuntimeError::InvalidUnaryOperation(
+                (*op).clone(),
+                format!("Operator can only be used on integers, found {}", right),
+            )),
+        },
+
+        _ => Err(RuntimeError::InvalidUnaryOperation((*op).clone(), "".to_string())),
+    }
+}
+
+pub fn binary(op: &Token, left: Object, right: Object) -> Result<Object, RuntimeError> {
+    let numbers_only = || {
+        RuntimeError::InvalidBinaryOperation(
+            (*op).clone(),
+            "Operator can only be used on numbers".to_string(),
+        )
+    };
+
+    match op.token_type {
+        TokenType::AND => {
+            if !left.is_truthy() {
+                return Ok(Object::Boolean { value: false });
+            }
+            Ok(Object::Boolean {
+                value: right.is_truthy(),
+            })
+        }
+
+        TokenType::OR => {
+            if left.is_truthy() {
+                return Ok(Object::Boolean { value: true });
+            }
+            Ok(Object::Boolean {
+                value: right.is_truthy(),
+            })
+        }
+
+        TokenType::PLUS => match (left, right) {
+            (Object::Number { value: l }, Object::Number { value: r }) => Ok(Object::Number { value: l + r }),
+            (Object::String { value: l }, Object::String { value: r }) => Ok(Object::String { value: l + &r }),
+            _ => Err(RuntimeError::InvalidBinaryOperation(
+                (*op).clone(),
+                "Operator can only be used on numbers or strings".to_string(),
+            )),
+        },
+
+        TokenType::MINUS => match (left, right) {
+            (Object::Number { value: l }, Object::Number { value: r }) => Ok(Object::Number { value: l - r }),
+            _ => Err(numbers_only()),
+        },
+
+        TokenType::STAR => match (left, right) {
+            (Object::Number { value: l }, Object::Number { value: r }) => Ok(Object::Number { value: l * r }),
+            _ => Err(numbers_only()),
+        },
+
+        TokenType::SLASH => match (left, right) {
+            (Object::Number { value: l }, Object::Number { value: r }) => Ok(Object::Number { value: l / r }),
+            _ => Err(numbers_only()),
+        },
+
+        // floored, so that `a == b * (a ~/ b) + a % b` with the remainder taking the sign of `b`
+        TokenType::TILDE_SLASH => match (left, right) {
+            (Object::Number { value: l }, Object::Number { value: r }) => Ok(Object::Number { value: (l / r).floor() }),
+            _ => Err(numbers_only()),
+        },
+
+        TokenType::PERCENT => match (left, right) {
+            (Object::Number { value: l }, Object::Number { value: r }) => Ok(Object::Number {
+                value: l - r * (l / r).floor(),
+            }),
+            _ => Err(numbers_only()),
+        },
+
+        TokenType::STAR_STAR => match (left, right) {
+            (Object::Number { value: l }, Object::Number { value: r }) => Ok(Object::Number { value: l.powf(r) }),
+            _ => Err(numbers_only()),
+        },
+
+        TokenType::AMPERSAND
+        | TokenType::PIPE
+        | TokenType::CARET
+        | TokenType::LESS_LESS
+        | TokenType::GREATER_GREATER => bitwise(op, &left, &right),
+
+        TokenType::GREATER => match (left, right) {
+            (Object::Number { value: l }, Object::Number { value: r }) => Ok(Object::Boolean { value: l > r }),
+            _ => Err(numbers_only()),
+        },
+
+        TokenType::GREATER_EQUAL => match (left, right) {
+            (Object::Number { value: l }, Object::Number { value: r }) => Ok(Object::Boolean { value: l >= r }),
+            _ => Err(numbers_only()),
+        },
+
+        TokenType::LESS => match (left, right) {
+            (Object::Number { value: l }, Object::Number { value: r }) => Ok(Object::Boolean { value: l < r }),
+            _ => Err(numbers_only()),
+        },
+
+        TokenType::LESS_EQUAL => match (left, right) {
+            (Object::Number { value: l }, Object::Number { value: r }) => Ok(Object::Boolean { value: l <= r }),
+            _ => Err(numbers_only()),
+        },
+
+        TokenType::EQUAL_EQUAL => Ok(Object::Boolean { value: left == right }),
+
+        TokenType::BANG_EQUAL => Ok(Object::Boolean { value: left != right }),
+
+        _ => Err(RuntimeError::InvalidBinaryOperation((*op).clone(), "".to_string())),
+    }
+}
+
+// the value of a number with no fractional part that fits in an i64
+fn integer(object: &Object) -> Option<i64> {
+    match object {
+        Object::Number { value }
+            if value.fract() == 0.0 && *value >= i64::MIN as f64 && *value < i64::MAX as f64 =>
+        {
+            Some(*value as i64)
+        }
+        _ => None,
+    }
+}
+
+fn bitwise(op: &Token, left: &Object, right: &Object) -> Result<Object, RuntimeError> {
+    let (l, r) = match (integer(left), integer(right)) {
+        (Some(l), Some(r)) => (l, r),
+        _ => {
+            let culprit = if integer(left).is_none() { left } else { right };
+            return Err(RuntimeError::InvalidBinaryOperation(
+                (*op).clone(),
+                format!("Operator can only be used on integers, found {}", culprit),
+            ));
+        }
+    };
+    let shift = || {
+        u32::try_from(r).ok().filter(|&r| r < 64).ok_or_else(|| {
+            RuntimeError::InvalidBinaryOperation(
+                (*op).clone(),
+                format!("Shift amount must be between 0 and 63, found {}", r),
+            )
+        })
+    };
+    let value = match op.token_type {
+        TokenType::AMPERSAND => l & r,
+        TokenType::PIPE => l | r,
+        TokenType::CARET => l ^ r,
+        TokenType::LESS_LESS => l << shift()?,
+        _ => l >> shift()?,
+    };
+    Ok(Object::Number { value: value as f64 })
+}
+
+// an interpolated string: every part as `print` would show it, run together
+pub fn interpolate(parts: Vec<Object>) -> Object {
+    Object::String {
+        value: parts.iter().map(Object::to_string).collect(),
+    }
+}
+
+pub fn get_property(object: Object, name: &Token) -> Result<Object, RuntimeError> {
+    match object {
+        Object::Instance { instance } => Instance::get(&instance, name),
+        list @ Object::List { .. } => match list::method(&name.lexeme) {
+            Some(method) => Ok(Object::Native {
+                func: method.bind(list),
+            }),
+            None => Err(RuntimeError::UndefinedProperty((*name).clone())),
+        },
+        Object::Module { module } => module.get(name),
+        map @ Object::Map { .. } => match map::method(&name.lexeme) {
+            Some(method) => Ok(Object::Native {
+                func: method.bind(map),
+            }),
+            None => Err(RuntimeError::UndefinedProperty((*name).clone())),
+        },
+        _ => Err(RuntimeError::InvalidPropertyAccess(
+            (*name).clone(),
+            "Only instances, lists, maps and modules have properties".to_string(),
+        )),
+    }
+}
+
+// `object.name = ...` is checked before the value is evaluated
+pub fn check_field_target(object: &Object, name: &Token) -> Result<(), RuntimeError> {
+    match object {
+        Object::Instance { .. } => Ok(()),
+        _ => Err(RuntimeError::InvalidPropertyAccess(
+            (*name).clone(),
+            "Only instances have fields".to_string(),
+        )),
+    }
+}
+
+pub fn index(object: Object, index: &Object, bracket: &Token) -> Result<Object, RuntimeError> {
+    match object {
+        Object::List { elements } => list::get(&elements, index, bracket),
+        Object::Map { entries } => map::get(&entries, index, bracket),
+        other => Err(RuntimeError::InvalidOperandType(
+            (*bracket).clone(),
+            format!("Cannot index into {}", type_name(&other)),
+        )),
+    }
+}
+
+pub fn set_index(object: Object, index: Object, value: Object, bracket: &Token) -> Result<Object, RuntimeError> {
+    match object {
+        Object::List { elements } => {
+            list::set(&elements, &index, value.clone(), bracket)?;
+            Ok(value)
+        }
+        Object::Map { entries } => {
+            map::set(&entries, index, value.clone(), bracket)?;
+            Ok(value)
+        }
+        other => Err(RuntimeError::InvalidOperandType(
+            (*bracket).clone(),
+            format!("Cannot assign into {}", type_name(&other)),
+        )),
+    }
+}
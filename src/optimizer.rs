@@ -0,0 +1,247 @@
+use crate::ast::expr::Expr;
+use crate::ast::stmt::Stmt;
+use crate::object::Object;
+use crate::ops;
+use crate::token::{Span, Token, TokenType};
+
+// AST-to-AST pass run after resolution. Operators whose operands are all
+// literals are folded into a single literal, and `if`/`while` statements with
+// a constant condition lose the branches that can never run. Anything that
+// would raise an error is left alone so the error still happens at runtime,
+// at the same place.
+pub fn optimize(stmts: Vec<Stmt>) -> Vec<Stmt> {
+    stmts.into_iter().filter_map(optimize_stmt).collect()
+}
+
+// `None` when the statement can never do anything
+fn optimize_stmt(stmt: Stmt) -> Option<Stmt> {
+    let stmt = match stmt {
+        Stmt::Block { stmts, span } => Stmt::Block {
+            stmts: optimize(stmts),
+            span,
+        },
+        Stmt::Expression { expr, span } => Stmt::Expression {
+            expr: optimize_expr(expr),
+            span,
+        },
+        Stmt::Class { name, superclass, methods, span } => Stmt::Class {
+            name,
+            superclass: superclass.map(optimize_expr),
+            methods: optimize(methods),
+            span,
+        },
+        Stmt::Function { name, params, body, span } => Stmt::Function {
+            name,
+            params,
+            body: optimize(body),
+            span,
+        },
+        Stmt::If { condition, then_branch, else_branch, span } => {
+            let condition = optimize_expr(condition);
+            if let Some(value) = constant(&condition) {
+                // the branch runs in the enclosing scope either way, so it can stand in for the `if`
+                return if value.is_truthy() {
+                    optimize_stmt(*then_branch)
+                } else {
+                    else_branch.and_then(|branch| optimize_stmt(*branch))
+                };
+            }
+            Stmt::If {
+                condition,
+                then_branch: Box::new(optimize_branch(*then_branch)),
+                else_branch: else_branch.and_then(|branch| optimize_stmt(*branch)).map(Box::new),
+                span,
+            }
+        }
+        Stmt::Print { expr, span } => Stmt::Print {
+            expr: optimize_expr(expr),
+            span,
+        },
+        Stmt::Return { keyword, value, span } => Stmt::Return {
+            keyword,
+            value: value.map(optimize_expr),
+            span,
+        },
+        Stmt::Throw { keyword, value, span } => Stmt::Throw {
+            keyword,
+            value: optimize_expr(value),
+            span,
+        },
+        Stmt::Try { body, catch_name, catch_body, finally_body, span } => Stmt::Try {
+            body: optimize(body),
+            catch_name,
+            catch_body: catch_body.map(optimize),
+            finally_body: finally_body.map(optimize),
+            span,
+        },
+        Stmt::Var { name, initializer, span } => Stmt::Var {
+            name,
+            initializer: initializer.map(optimize_expr),
+            span,
+        },
+        Stmt::While { condition, body, increment, per_iteration, span } => {
+            let condition = optimize_expr(condition);
+            if let Some(value) = constant(&condition) {
+                if !value.is_truthy() {
+                    return None;
+                }
+            }
+            Stmt::While {
+                condition,
+                body: Box::new(optimize_branch(*body)),
+                increment: increment.map(optimize_expr),
+                per_iteration,
+                span,
+            }
+        }
+        stmt @ (Stmt::Break { .. } | Stmt::Continue { .. } | Stmt::Import { .. }) => stmt,
+    };
+    Some(stmt)
+}
+
+// a statement that has to stay, even if it ends up doing nothing
+fn optimize_branch(stmt: Stmt) -> Stmt {
+    let span = stmt.span();
+    optimize_stmt(stmt).unwrap_or(Stmt::Block { stmts: Vec::new(), span })
+}
+
+fn optimize_expr(expr: Expr) -> Expr {
+    match expr {
+        Expr::Unary { op, value } => {
+            let value = optimize_expr(*value);
+            let span = op.span.to(&value.span());
+            if let Some(folded) = constant(&value).and_then(|value| ops::unary(&op, value).ok()) {
+                return literal(folded, span, op.line);
+            }
+            Expr::Unary { op, value: Box::new(value) }
+        }
+        Expr::Binary { op, left, right } => {
+            let left = optimize_expr(*left);
+            let right = optimize_expr(*right);
+            if let (Some(l), Some(r)) = (constant(&left), constant(&right)) {
+                if let Ok(folded) = ops::binary(&op, l, r) {
+                    return literal(folded, left.span().to(&right.span()), op.line);
+                }
+            }
+            Expr::Binary {
+                op,
+                left: Box::new(left),
+                right: Box::new(right),
+            }
+        }
+        Expr::Logical { op, left, right } => {
+            let left = optimize_expr(*left);
+            let right = optimize_expr(*right);
+            // a short-circuit yields a boolean; otherwise the result is the right operand's value
+            if let Some(value) = constant(&left) {
+                let span = left.span().to(&right.span());
+                return match (op.token_type, value.is_truthy()) {
+                    (TokenType::AND, false) => literal(Object::Boolean { value: false }, span, op.line),
+                    (TokenType::OR, true) => literal(Object::Boolean { value: true }, span, op.line),
+                    _ => right,
+                };
+            }
+            Expr::Logical {
+                op,
+                left: Box::new(left),
+                right: Box::new(right),
+            }
+        }
+        Expr::Grouping { expr, span } => {
+            let expr = optimize_expr(*expr);
+            match constant(&expr) {
+                Some(value) => literal(value, span, span.line),
+                None => Expr::Grouping { expr: Box::new(expr), span },
+            }
+        }
+        Expr::Assign { name, value, id } => Expr::Assign {
+            name,
+            value: Box::new(optimize_expr(*value)),
+            id,
+        },
+        Expr::Call { callee, paren, arguments } => Expr::Call {
+            callee: Box::new(optimize_expr(*callee)),
+            paren,
+            arguments: arguments.into_iter().map(optimize_expr).collect(),
+        },
+        Expr::Set { object, name, value } => Expr::Set {
+            object: Box::new(optimize_expr(*object)),
+            name,
+            value: Box::new(optimize_expr(*value)),
+        },
+        Expr::Get { object, name } => Expr::Get {
+            object: Box::new(optimize_expr(*object)),
+            name,
+        },
+        Expr::List { bracket, elements, span } => Expr::List {
+            bracket,
+            elements: elements.into_iter().map(optimize_expr).collect(),
+            span,
+        },
+        Expr::Map { brace, entries, span } => Expr::Map {
+            brace,
+            entries: entries
+                .into_iter()
+                .map(|(key, value)| (optimize_expr(key), optimize_expr(value)))
+                .collect(),
+            span,
+        },
+        Expr::Index { object, bracket, index, span } => Expr::Index {
+            object: Box::new(optimize_expr(*object)),
+            bracket,
+            index: Box::new(optimize_expr(*index)),
+            span,
+        },
+        Expr::SetIndex { object, bracket, index, value } => Expr::SetIndex {
+            object: Box::new(optimize_expr(*object)),
+            bracket,
+            index: Box::new(optimize_expr(*index)),
+            value: Box::new(optimize_expr(*value)),
+        },
+        Expr::Interpolation { start, parts, span } => {
+            let parts: Vec<Expr> = parts.into_iter().map(optimize_expr).collect();
+            match parts.iter().map(constant).collect::<Option<Vec<Object>>>() {
+                Some(values) => literal(ops::interpolate(values), span, start.line),
+                None => Expr::Interpolation { start, parts, span },
+            }
+        }
+        Expr::Update { target, op, value, postfix, span } => Expr::Update {
+            target: Box::new(optimize_expr(*target)),
+            op,
+            value: Box::new(optimize_expr(*value)),
+            postfix,
+            span,
+        },
+        Expr::Lambda { name, params, body, span } => Expr::Lambda {
+            name,
+            params,
+            body: optimize(body),
+            span,
+        },
+        expr @ (Expr::Literal { .. } | Expr::Variable { .. } | Expr::This { .. } | Expr::Super { .. }) => expr,
+    }
+}
+
+fn constant(expr: &Expr) -> Option<Object> {
+    match expr {
+        Expr::Literal { value } => ops::literal(value).ok(),
+        _ => None,
+    }
+}
+
+// a literal standing in for the folded expression, covering all of its source
+fn literal(value: Object, span: Span, line: usize) -> Expr {
+    let (token_type, lexeme) = match &value {
+        Object::Number { value } => (TokenType::NUMBER, value.to_string()),
+        Object::String { value } => (TokenType::STRING, format!("{:?}", value)),
+        Object::Boolean { value: true } => (TokenType::TRUE, "true".to_string()),
+        Object::Boolean { value: false } => (TokenType::FALSE, "false".to_string()),
+        _ => (TokenType::NULL, "null".to_string()),
+    };
+    let mut token = Token::new(token_type, lexeme, line);
+    if let Object::String { value } = value {
+        token = token.with_literal(value);
+    }
+    token.span = span;
+    Expr::Literal { value: token }
+}
@@ -1,12 +1,17 @@
 use crate::ast::expr::Expr;
 use crate::ast::stmt::Stmt;
 use crate::error::ParserError;
-use crate::token::{Token, TokenType};
+use crate::module;
+use crate::token::{Span, Token, TokenType};
+
+// past this many errors the rest of the file is not worth parsing
+const MAX_ERRORS: usize = 20;
 
 pub struct Parser {
     tokens: Vec<Token>,
     current: usize,
-    pub error_count: u32,
+    loop_depth: usize,
+    errors: Vec<ParserError>,
 }
 
 impl Parser {
@@ -14,42 +19,108 @@ impl Parser {
         Parser {
             tokens,
             current: 0,
-            error_count: 0,
+            loop_depth: 0,
+            errors: Vec::new(),
         }
     }
 
-    pub fn parse(&mut self) -> Result<Vec<Stmt>, ParserError> {
+    // Parses the whole program, recovering after each syntax error so that every
+    // error in the file is reported, not just the first.
+    pub fn parse(&mut self) -> Result<Vec<Stmt>, Vec<ParserError>> {
         let mut stmts: Vec<Stmt> = Vec::new();
         while !self.is_end() {
-            let stmt = self.declaration();
-            match stmt {
+            let start = self.current;
+            match self.declaration() {
                 Ok(stmt) => stmts.push(stmt),
-                Err(e) => {
-                    self.error_count += 1;
-                    eprintln!("{}", e);
+                Err(e) => self.recover(e, start),
+            }
+        }
+        if self.errors.is_empty() {
+            Ok(stmts)
+        } else {
+            Err(std::mem::take(&mut self.errors))
+        }
+    }
+
+    // Panic mode: records the error, then throws away tokens until the start of the
+    // next statement so the parser can carry on from a known state. `start` is where
+    // the failed declaration began.
+    fn recover(&mut self, error: ParserError, start: usize) {
+        // once the cap is hit we have jumped to the end; errors unwinding from there add nothing
+        if self.errors.len() >= MAX_ERRORS {
+            return;
+        }
+        self.errors.push(error);
+        if self.errors.len() == MAX_ERRORS {
+            self.errors.push(ParserError::TooManyErrors(self.prev_span(), MAX_ERRORS));
+            self.current = self.tokens.len() - 1;
+            return;
+        }
+        // the token it failed on could itself start a statement, which would send
+        // us straight back into the same error
+        if self.current == start {
+            self.advance();
+        }
+        self.synchronize();
+    }
+
+    // the failed declaration has consumed at least one token by now, so a `;` just
+    // behind us is the end of the broken statement rather than of an earlier one
+    fn synchronize(&mut self) {
+        while !self.is_end() {
+            if self.view_prev().map_or(false, |token| token.token_type == TokenType::SEMICOLON) {
+                return;
+            }
+            match self.peek().unwrap().token_type {
+                TokenType::VAR
+                | TokenType::FN
+                | TokenType::CLASS
+                | TokenType::IF
+                | TokenType::WHILE
+                | TokenType::FOR
+                | TokenType::RETURN
+                | TokenType::PRINT
+                | TokenType::IMPORT
+                | TokenType::THROW
+                | TokenType::TRY
+                | TokenType::BREAK
+                | TokenType::CONTINUE
+                | TokenType::RIGHT_BRACE => return,
+                // a body after the broken part belongs to it, so skip it whole
+                TokenType::LEFT_BRACE => self.skip_braces(),
+                _ => {
+                    self.advance();
                 }
             }
         }
-        Ok(stmts)
+    }
+
+    fn skip_braces(&mut self) {
+        let mut depth = 0;
+        while !self.is_end() {
+            match self.advance().unwrap().token_type {
+                TokenType::LEFT_BRACE => depth += 1,
+                TokenType::RIGHT_BRACE => depth -= 1,
+                _ => {}
+            }
+            if depth == 0 {
+                return;
+            }
+        }
     }
 
     fn declaration(&mut self) -> Result<Stmt, ParserError> {
-        let stmt = if self.match_token(TokenType::VAR) {
+        if self.match_token(TokenType::VAR) {
             self.var_declaration()
         } else if self.match_token(TokenType::CLASS) {
             self.class_declaration()
-        } else if self.match_token(TokenType::FN) {
+        } else if self.check_type(TokenType::FN) && self.check_next(TokenType::IDENTIFIER) {
+            self.advance();
             self.function_declaration()
+        } else if self.match_token(TokenType::IMPORT) {
+            self.import_declaration()
         } else {
             self.statement()
-        };
-
-        match stmt {
-            Ok(stmt) => Ok(stmt),
-            Err(e) => {
-                self.error_count += 1;
-                Err(e)
-            }
         }
     }
     fn statement(&mut self) -> Result<Stmt, ParserError> {
@@ -63,9 +134,19 @@ impl Parser {
             self.while_statement()
         } else if self.match_token(TokenType::RETURN){
             self.return_statement()
+        } else if self.match_token(TokenType::THROW) {
+            self.throw_statement()
+        } else if self.match_token(TokenType::TRY) {
+            self.try_statement()
+        } else if self.match_token(TokenType::BREAK) {
+            self.break_statement()
+        } else if self.match_token(TokenType::CONTINUE) {
+            self.continue_statement()
         } else if self.match_token(TokenType::LEFT_BRACE) {
+            let start = self.prev_span();
             Ok(Stmt::Block {
                 stmts: self.block()?,
+                span: self.span_from(start),
             })
         } else {
             self.expression_statement()
@@ -73,6 +154,7 @@ impl Parser {
     }
 
     fn print_statement(&mut self) -> Result<Stmt, ParserError> {
+        let start = self.prev_span();
         self.consume(
             TokenType::LEFT_PAREN,
             "Expect '(' after 'print'.".to_string(),
@@ -85,10 +167,12 @@ impl Parser {
         self.consume(TokenType::SEMICOLON, "Expect ';' after value.".to_string())?;
         Ok(Stmt::Print {
             expr,
+            span: self.span_from(start),
         })
     }
 
     fn if_statement(&mut self) -> Result<Stmt, ParserError> {
+        let start = self.prev_span();
         self.consume(TokenType::LEFT_PAREN, "Expect '(' after 'if'.".to_string())?;
         let condition = self.parse_expr()?;
         self.consume(
@@ -106,10 +190,12 @@ impl Parser {
             condition,
             then_branch,
             else_branch,
+            span: self.span_from(start),
         })
     }
 
     fn while_statement(&mut self) -> Result<Stmt, ParserError> {
+        let start = self.prev_span();
         self.consume(
             TokenType::LEFT_PAREN,
             "Expect '(' after 'while'.".to_string(),
@@ -120,13 +206,47 @@ impl Parser {
             "Expect ')' after while condition.".to_string(),
         )?;
 
-        let body = Box::new(self.statement()?);
+        let body = Box::new(self.loop_body()?);
 
         Ok(Stmt::While {
             condition,
             body,
+            increment: None,
+            per_iteration: false,
+            span: self.span_from(start),
         })
     }
+
+    fn loop_body(&mut self) -> Result<Stmt, ParserError> {
+        self.loop_depth += 1;
+        let body = self.statement();
+        self.loop_depth -= 1;
+        body
+    }
+
+    fn break_statement(&mut self) -> Result<Stmt, ParserError> {
+        let keyword = self.view_prev().unwrap();
+        self.consume(TokenType::SEMICOLON, "Expect ';' after 'break'.".to_string())?;
+        if self.loop_depth == 0 {
+            return Err(ParserError::UnexpectedToken(
+                keyword.span,
+                "Can't use 'break' outside of a loop.".to_string(),
+            ));
+        }
+        Ok(Stmt::Break { span: self.span_from(keyword.span), keyword })
+    }
+
+    fn continue_statement(&mut self) -> Result<Stmt, ParserError> {
+        let keyword = self.view_prev().unwrap();
+        self.consume(TokenType::SEMICOLON, "Expect ';' after 'continue'.".to_string())?;
+        if self.loop_depth == 0 {
+            return Err(ParserError::UnexpectedToken(
+                keyword.span,
+                "Can't use 'continue' outside of a loop.".to_string(),
+            ));
+        }
+        Ok(Stmt::Continue { span: self.span_from(keyword.span), keyword })
+    }
     
     fn return_statement(&mut self) -> Result<Stmt, ParserError> {
         let keyword = self.view_prev().unwrap().clone();
@@ -137,12 +257,61 @@ impl Parser {
         };
         self.consume(TokenType::SEMICOLON, "Expect ';' after return value.".to_string())?;
         Ok(Stmt::Return {
+            span: self.span_from(keyword.span),
             keyword,
             value,
         })
     }
 
+    fn throw_statement(&mut self) -> Result<Stmt, ParserError> {
+        let keyword = self.view_prev().unwrap();
+        let value = self.parse_expr()?;
+        self.consume(TokenType::SEMICOLON, "Expect ';' after thrown value.".to_string())?;
+        Ok(Stmt::Throw { span: self.span_from(keyword.span), keyword, value })
+    }
+
+    fn try_statement(&mut self) -> Result<Stmt, ParserError> {
+        let keyword = self.view_prev().unwrap();
+        self.consume(TokenType::LEFT_BRACE, "Expect '{' after 'try'.".to_string())?;
+        let body = self.block()?;
+
+        let mut catch_name: Option<Token> = None;
+        let mut catch_body: Option<Vec<Stmt>> = None;
+        if self.match_token(TokenType::CATCH) {
+            self.consume(TokenType::LEFT_PAREN, "Expect '(' after 'catch'.".to_string())?;
+            catch_name = Some(self.consume(
+                TokenType::IDENTIFIER,
+                "Expect name for the caught value.".to_string(),
+            )?);
+            self.consume(TokenType::RIGHT_PAREN, "Expect ')' after catch name.".to_string())?;
+            self.consume(TokenType::LEFT_BRACE, "Expect '{' before catch body.".to_string())?;
+            catch_body = Some(self.block()?);
+        }
+
+        let mut finally_body: Option<Vec<Stmt>> = None;
+        if self.match_token(TokenType::FINALLY) {
+            self.consume(TokenType::LEFT_BRACE, "Expect '{' after 'finally'.".to_string())?;
+            finally_body = Some(self.block()?);
+        }
+
+        if catch_body.is_none() && finally_body.is_none() {
+            return Err(ParserError::UnexpectedToken(
+                keyword.span,
+                "Expect 'catch' or 'finally' after try block.".to_string(),
+            ));
+        }
+
+        Ok(Stmt::Try {
+            body,
+            catch_name,
+            catch_body,
+            finally_body,
+            span: self.span_from(keyword.span),
+        })
+    }
+
     fn for_statement(&mut self) -> Result<Stmt, ParserError> {
+        let start = self.prev_span();
         self.consume(TokenType::LEFT_PAREN, "Expect '(' after 'for'.".to_string())?;
 
         let initializer = if self.match_token(TokenType::SEMICOLON) {
@@ -174,19 +343,9 @@ impl Parser {
             "Expect ')' after for clauses.".to_string(),
         )?;
 
-        let mut body = self.statement()?;
-
-        if let Some(increment) = increment {
-            body = Stmt::Block {
-                stmts: vec![
-                    body,
-                    Stmt::Expression {
-                        expr: increment,
-                    },
-                ],
-            };
-        }
+        let mut body = self.loop_body()?;
 
+        let per_iteration = matches!(initializer, Some(Stmt::Var { .. }));
         body = Stmt::While {
             condition: condition.unwrap_or(Expr::Literal {
                 value: Token::new(
@@ -196,8 +355,18 @@ impl Parser {
                 ),
             }),
             body: Box::new(body),
+            increment,
+            per_iteration,
+            span: self.span_from(start),
         };
 
+        if let Some(initializer) = initializer {
+            body = Stmt::Block {
+                stmts: vec![initializer, body],
+                span: self.span_from(start),
+            };
+        }
+
         Ok(body)
     }
 
@@ -208,11 +377,13 @@ impl Parser {
             "Expect ';' after expression.".to_string(),
         )?;
         Ok(Stmt::Expression {
+            span: self.span_from(expr.span()),
             expr,
         })
     }
 
     fn var_declaration(&mut self) -> Result<Stmt, ParserError> {
+        let start = self.prev_span();
         let name = match self.consume(TokenType::IDENTIFIER, "Expect variable name.".to_string()) {
             Ok(token) => token,
             Err(e) => return Err(e),
@@ -229,45 +400,103 @@ impl Parser {
             "Expect ';' after variable declaration.".to_string(),
         )?;
 
-        Ok(Stmt::Var { name, initializer })
+        Ok(Stmt::Var { name, initializer, span: self.span_from(start) })
+    }
+
+    fn import_declaration(&mut self) -> Result<Stmt, ParserError> {
+        let keyword = self.view_prev().unwrap();
+        let path = self.consume(TokenType::STRING, "Expect module path after 'import'.".to_string())?;
+
+        // `as` is contextual so it stays usable as an ordinary identifier
+        let name = if self.check_type(TokenType::IDENTIFIER) && self.peek().unwrap().lexeme == "as" {
+            self.advance();
+            self.consume(TokenType::IDENTIFIER, "Expect module name after 'as'.".to_string())?
+        } else {
+            match module::default_name(path.value()) {
+                Some(stem) => {
+                    let mut name = Token::new(TokenType::IDENTIFIER, stem, path.line);
+                    name.span = path.span;
+                    name
+                }
+                None => {
+                    return Err(ParserError::UnexpectedToken(
+                        path.span,
+                        format!("Module {} needs a name; use 'import {} as name;'.", path.lexeme, path.lexeme),
+                    ))
+                }
+            }
+        };
+        self.consume(TokenType::SEMICOLON, "Expect ';' after import.".to_string())?;
+
+        Ok(Stmt::Import { span: self.span_from(keyword.span), keyword, path, name })
     }
 
     fn class_declaration(&mut self) -> Result<Stmt, ParserError> {
+        let start = self.prev_span();
         let name = match self.consume(TokenType::IDENTIFIER, "Expect class name.".to_string()) {
             Ok(token) => token,
             Err(e) => return Err(e),
         };
+        let mut superclass: Option<Expr> = None;
+        if self.match_token(TokenType::LESS) {
+            let super_name =
+                self.consume(TokenType::IDENTIFIER, "Expect superclass name.".to_string())?;
+            if super_name.lexeme == name.lexeme {
+                return Err(ParserError::InvalidExpression(
+                    super_name.span,
+                    "A class can't inherit from itself.".to_string(),
+                ));
+            }
+            superclass = Some(Expr::Variable {
+                name: super_name,
+                id: Expr::next_id(),
+            });
+        }
+        self.consume(
+            TokenType::LEFT_BRACE,
+            "Expect '{' before class body.".to_string(),
+        )?;
         let mut methods: Vec<Stmt> = Vec::new();
         while !self.check_type(TokenType::EOF) && !self.check_type(TokenType::RIGHT_BRACE) {
-            let method = self.function_declaration();
-            match method {
+            let start = self.current;
+            match self.function_declaration() {
                 Ok(method) => methods.push(method),
-                Err(e) => {
-                    self.error_count += 1;
-                    eprintln!("{}", e);
-                }
+                Err(e) => self.recover(e, start),
             }
         }
         self.consume(
             TokenType::RIGHT_BRACE,
             "Expected } after class declaration".to_string(),
         )?;
-        Ok(Stmt::Class { name, methods })
+        Ok(Stmt::Class { name, superclass, methods, span: self.span_from(start) })
     }
 
     fn function_declaration(&mut self) -> Result<Stmt, ParserError> {
+        let keyword = self.view_prev().unwrap();
         let name = self.consume(TokenType::IDENTIFIER, "Expect function name.".to_string())?;
+        // methods have no `fn` keyword and start at their name
+        let start = if keyword.token_type == TokenType::FN { keyword.span } else { name.span };
         self.consume(
             TokenType::LEFT_PAREN,
             "Expect '(' after function name.".to_string(),
         )?;
+        let params = self.parameters()?;
+        self.consume(
+            TokenType::LEFT_BRACE,
+            "Expect '{' before function body.".to_string(),
+        )?;
+        let body = self.function_body()?;
+        Ok(Stmt::Function { name, params, body, span: self.span_from(start) })
+    }
 
+    // the parameter list after its `(`, up to and including the `)`
+    fn parameters(&mut self) -> Result<Vec<Token>, ParserError> {
         let mut params: Vec<Token> = Vec::new();
         if !self.check_type(TokenType::RIGHT_PAREN) {
             loop {
                 if params.len() >= 255 {
                     return Err(ParserError::InvalidExpression(
-                        self.peek().unwrap().line,
+                        self.peek().unwrap().span,
                         "Cannot have more than 255 parameters.".to_string(),
                     ));
                 }
@@ -284,20 +513,117 @@ impl Parser {
             TokenType::RIGHT_PAREN,
             "Expect ')' after parameters.".to_string(),
         )?;
+        Ok(params)
+    }
+
+    // a function body after its `{`
+    fn function_body(&mut self) -> Result<Vec<Stmt>, ParserError> {
+        // a loop around the function does not make `break` valid inside the body
+        let enclosing_loop_depth = self.loop_depth;
+        self.loop_depth = 0;
+        let body = self.block();
+        self.loop_depth = enclosing_loop_depth;
+        body
+    }
+
+    // `fn (a, b) { ... }`, with the `fn` already consumed
+    fn lambda(&mut self, keyword: Token) -> Result<Expr, ParserError> {
+        self.consume(TokenType::LEFT_PAREN, "Expect '(' after 'fn'.".to_string())?;
+        let params = self.parameters()?;
         self.consume(
             TokenType::LEFT_BRACE,
             "Expect '{' before function body.".to_string(),
         )?;
+        let body = self.function_body()?;
+        Ok(Expr::Lambda {
+            name: lambda_name(&keyword),
+            params,
+            body,
+            span: self.span_from(keyword.span),
+        })
+    }
+
+    // `(a, b) => a + b` or `(a, b) => { ... }`, with the `(` already consumed
+    fn arrow_function(&mut self, paren: Token) -> Result<Expr, ParserError> {
+        let params = self.parameters()?;
+        let arrow = self.consume(TokenType::ARROW, "Expect '=>' after parameters.".to_string())?;
+        let body = if self.match_token(TokenType::LEFT_BRACE) {
+            self.function_body()?
+        } else {
+            // a body that is just an expression returns it
+            let enclosing_loop_depth = self.loop_depth;
+            self.loop_depth = 0;
+            let value = self.parse_expr();
+            self.loop_depth = enclosing_loop_depth;
+            let value = value?;
+            vec![Stmt::Return { span: value.span(), keyword: arrow, value: Some(value) }]
+        };
+        Ok(Expr::Lambda {
+            name: lambda_name(&paren),
+            params,
+            body,
+            span: self.span_from(paren.span),
+        })
+    }
 
-        let mut body = self.block()?;
-        Ok(Stmt::Function { name, params, body })
+    // `INTERPOLATION expr (INTERPOLATION expr)* STRING`, as the lexer splits up `"a ${b} c"`
+    fn interpolation(&mut self) -> Result<Expr, ParserError> {
+        let start = self.peek().unwrap();
+        let mut parts: Vec<Expr> = Vec::new();
+        loop {
+            let mut text = self.advance().unwrap();
+            let done = text.token_type == TokenType::STRING;
+            if !text.value().is_empty() {
+                text.token_type = TokenType::STRING;
+                parts.push(Expr::Literal { value: text });
+            }
+            if done {
+                break;
+            }
+            parts.push(self.parse_expr()?);
+            if !self.check_type(TokenType::INTERPOLATION) && !self.check_type(TokenType::STRING) {
+                return Err(ParserError::UnexpectedToken(
+                    self.peek().unwrap().span,
+                    "Expect '}' after interpolated expression.".to_string(),
+                ));
+            }
+        }
+        Ok(Expr::Interpolation {
+            span: self.span_from(start.span),
+            start,
+            parts,
+        })
+    }
+
+    // whether the `(` about to be read opens the parameter list of an arrow function
+    fn at_arrow_function(&self) -> bool {
+        let type_at = |i: usize| self.tokens.get(i).map(|token| token.token_type.clone());
+        let mut i = self.current + 1;
+        if type_at(i) != Some(TokenType::RIGHT_PAREN) {
+            loop {
+                if type_at(i) != Some(TokenType::IDENTIFIER) {
+                    return false;
+                }
+                i += 1;
+                match type_at(i) {
+                    Some(TokenType::COMMA) => i += 1,
+                    Some(TokenType::RIGHT_PAREN) => break,
+                    _ => return false,
+                }
+            }
+        }
+        type_at(i + 1) == Some(TokenType::ARROW)
     }
 
     fn block(&mut self) -> Result<Vec<Stmt>, ParserError> {
         let mut statements: Vec<Stmt> = Vec::new();
 
         while !self.check_type(TokenType::RIGHT_BRACE) && !self.is_end() {
-            statements.push(self.declaration()?);
+            let start = self.current;
+            match self.declaration() {
+                Ok(stmt) => statements.push(stmt),
+                Err(e) => self.recover(e, start),
+            }
         }
 
         self.consume(
@@ -321,7 +647,16 @@ impl Parser {
         None
     }
 
-    fn is_end(&self) -> bool {
+    fn prev_span(&self) -> Span {
+        self.view_prev().map_or(Span::default(), |token| token.span)
+    }
+
+    // from `start` to the end of the last consumed token
+    fn span_from(&self, start: Span) -> Span {
+        start.to(&self.prev_span())
+    }
+
+    pub fn is_end(&self) -> bool {
         if let Some(token) = self.peek() {
             return token.token_type == TokenType::EOF;
         } else {
@@ -342,9 +677,8 @@ impl Parser {
             return Ok(self.advance().unwrap());
         }
 
-        self.error_count += 1;
         Err(ParserError::UnexpectedToken(
-            self.view_prev().unwrap().line,
+            self.peek().map_or(self.prev_span(), |token| token.span),
             if_error_msg,
         ))
     }
@@ -366,6 +700,10 @@ impl Parser {
         false
     }
 
+    fn check_next(&self, expected_type: TokenType) -> bool {
+        self.tokens.get(self.current + 1).map_or(false, |token| token.token_type == expected_type)
+    }
+
     fn match_token(&mut self, token_type: TokenType) -> bool {
         if self.check_type(token_type) {
             self.advance();
@@ -376,6 +714,11 @@ impl Parser {
 
     fn parse_expr_primary(&mut self) -> Result<Expr, ParserError> {
         match self.peek().clone() {
+            // the rest of a string after a `${...}`: the expression stopped short of its `}`
+            Some(token) if is_interpolation_tail(&token) => Err(ParserError::InvalidExpression(
+                Span { end: token.span.start + 1, ..token.span },
+                "Expect expression inside '${...}'.".to_string(),
+            )),
             Some(token) => match token.token_type {
                 TokenType::STRING
                 | TokenType::NUMBER
@@ -387,13 +730,21 @@ impl Parser {
                     Ok(Expr::Literal { value: token })
                 }
 
+                TokenType::LEFT_PAREN if self.at_arrow_function() => {
+                    let paren = self.advance().unwrap();
+                    self.arrow_function(paren)
+                }
+                TokenType::FN => {
+                    let keyword = self.advance().unwrap();
+                    self.lambda(keyword)
+                }
+                TokenType::INTERPOLATION => self.interpolation(),
                 TokenType::LEFT_PAREN => {
                     self.advance();
                     if self.check_type(TokenType::RIGHT_PAREN) {
                         self.advance();
-                        self.error_count += 1;
                         return Err(ParserError::InvalidExpression(
-                            token.line,
+                            token.span,
                             "Expected expression inside parentheses".to_string(),
                         ));
                     } else {
@@ -413,18 +764,76 @@ impl Parser {
                         };
                         return Ok(Expr::Grouping {
                             expr: Box::new(unwrapped_expr),
+                            span: self.span_from(token.span),
                         });
                     }
                 }
                 TokenType::IDENTIFIER => {
                     let token = self.advance().unwrap();
-                    Ok(Expr::Variable { name: token })
+                    Ok(Expr::Variable {
+                        name: token,
+                        id: Expr::next_id(),
+                    })
+                }
+                TokenType::THIS => {
+                    let keyword = self.advance().unwrap();
+                    Ok(Expr::This {
+                        keyword,
+                        id: Expr::next_id(),
+                    })
+                }
+                TokenType::LEFT_BRACKET => {
+                    let bracket = self.advance().unwrap();
+                    let mut elements: Vec<Expr> = Vec::new();
+                    while !self.check_type(TokenType::RIGHT_BRACKET) {
+                        elements.push(self.parse_expr()?);
+                        if !self.match_token(TokenType::COMMA) {
+                            break;
+                        }
+                    }
+                    self.consume(
+                        TokenType::RIGHT_BRACKET,
+                        "Expect ']' after list elements.".to_string(),
+                    )?;
+                    let span = self.span_from(bracket.span);
+                    Ok(Expr::List { bracket, elements, span })
+                }
+                TokenType::LEFT_BRACE => {
+                    let brace = self.advance().unwrap();
+                    let mut entries: Vec<(Expr, Expr)> = Vec::new();
+                    while !self.check_type(TokenType::RIGHT_BRACE) {
+                        let key = self.parse_expr()?;
+                        self.consume(TokenType::COLON, "Expect ':' after map key.".to_string())?;
+                        let value = self.parse_expr()?;
+                        entries.push((key, value));
+                        if !self.match_token(TokenType::COMMA) {
+                            break;
+                        }
+                    }
+                    self.consume(
+                        TokenType::RIGHT_BRACE,
+                        "Expect '}' after map entries.".to_string(),
+                    )?;
+                    let span = self.span_from(brace.span);
+                    Ok(Expr::Map { brace, entries, span })
+                }
+                TokenType::SUPER => {
+                    let keyword = self.advance().unwrap();
+                    self.consume(TokenType::DOT, "Expect '.' after 'super'.".to_string())?;
+                    let method = self.consume(
+                        TokenType::IDENTIFIER,
+                        "Expect superclass method name.".to_string(),
+                    )?;
+                    Ok(Expr::Super {
+                        keyword,
+                        method,
+                        id: Expr::next_id(),
+                    })
                 }
                 _ => {
-                    self.error_count += 1;
                     self.advance();
                     Err(ParserError::UnexpectedToken(
-                        token.line,
+                        token.span,
                         token.lexeme.to_string(),
                     ))
                 }
@@ -453,26 +862,70 @@ impl Parser {
 
     fn parse_assign(&mut self) -> Result<Expr, ParserError> {
         let expr: Expr = self.parse_or()?;
+        if let Some(op) = self.match_compound_assign() {
+            let value: Expr = self.parse_assign()?;
+            return update(expr, op, value, false);
+        }
         if self.match_token(TokenType::EQUAL) {
             let eq: Token = self.view_prev().clone().unwrap();
             let value: Expr = self.parse_assign()?;
 
-            if let Expr::Variable { name } = expr {
+            if let Expr::Variable { name, id } = expr {
                 return Ok(Expr::Assign {
                     name,
                     value: Box::new(value),
+                    id,
+                });
+            } else if let Expr::Get { object, name } = expr {
+                return Ok(Expr::Set {
+                    object,
+                    name,
+                    value: Box::new(value),
+                });
+            } else if let Expr::Index { object, bracket, index, .. } = expr {
+                return Ok(Expr::SetIndex {
+                    object,
+                    bracket,
+                    index,
+                    value: Box::new(value),
                 });
             } else {
-                self.error_count += 1;
                 return Err(ParserError::UnexpectedToken(
-                    eq.line,
-                    "Expected variable name".to_string(),
+                    eq.span,
+                    "Invalid assignment target".to_string(),
                 ));
             }
         }
         Ok(expr)
     }
 
+    // a `+=`-style operator, retyped as the binary operator it applies
+    fn match_compound_assign(&mut self) -> Option<Token> {
+        let token_type = match self.peek()?.token_type {
+            TokenType::PLUS_EQUAL => TokenType::PLUS,
+            TokenType::MINUS_EQUAL => TokenType::MINUS,
+            TokenType::STAR_EQUAL => TokenType::STAR,
+            TokenType::SLASH_EQUAL => TokenType::SLASH,
+            TokenType::PERCENT_EQUAL => TokenType::PERCENT,
+            _ => return None,
+        };
+        let mut op = self.advance().unwrap();
+        op.token_type = token_type;
+        Some(op)
+    }
+
+    // `++` or `--`, retyped as the `+` or `-` it applies
+    fn match_increment(&mut self) -> Option<Token> {
+        let token_type = match self.peek()?.token_type {
+            TokenType::PLUS_PLUS => TokenType::PLUS,
+            TokenType::MINUS_MINUS => TokenType::MINUS,
+            _ => return None,
+        };
+        let mut op = self.advance().unwrap();
+        op.token_type = token_type;
+        Some(op)
+    }
+
     fn parse_or(&mut self) -> Result<Expr, ParserError> {
         let mut expr: Expr = self.parse_and()?;
         while self.match_token(TokenType::OR) {
@@ -502,7 +955,7 @@ impl Parser {
     }
 
     fn parse_compare(&mut self) -> Result<Expr, ParserError> {
-        let mut expr: Expr = self.parse_term()?;
+        let mut expr: Expr = self.parse_bit_or()?;
 
         while self.match_token(TokenType::LESS)
             || self.match_token(TokenType::LESS_EQUAL)
@@ -510,7 +963,7 @@ impl Parser {
             || self.match_token(TokenType::GREATER_EQUAL)
         {
             let op: Token = self.view_prev().unwrap();
-            let right: Expr = self.parse_term()?;
+            let right: Expr = self.parse_bit_or()?;
             expr = Expr::Binary {
                 op,
                 left: Box::new(expr),
@@ -520,6 +973,63 @@ impl Parser {
         Ok(expr)
     }
 
+    // the bitwise operators sit between comparison and arithmetic, loosest first: `|`, `^`, `&`, shifts
+    fn parse_bit_or(&mut self) -> Result<Expr, ParserError> {
+        let mut expr: Expr = self.parse_bit_xor()?;
+        while self.match_token(TokenType::PIPE) {
+            let op: Token = self.view_prev().unwrap();
+            let right: Expr = self.parse_bit_xor()?;
+            expr = Expr::Binary {
+                op,
+                left: Box::new(expr),
+                right: Box::new(right),
+            };
+        }
+        Ok(expr)
+    }
+
+    fn parse_bit_xor(&mut self) -> Result<Expr, ParserError> {
+        let mut expr: Expr = self.parse_bit_and()?;
+        while self.match_token(TokenType::CARET) {
+            let op: Token = self.view_prev().unwrap();
+            let right: Expr = self.parse_bit_and()?;
+            expr = Expr::Binary {
+                op,
+                left: Box::new(expr),
+                right: Box::new(right),
+            };
+        }
+        Ok(expr)
+    }
+
+    fn parse_bit_and(&mut self) -> Result<Expr, ParserError> {
+        let mut expr: Expr = self.parse_shift()?;
+        while self.match_token(TokenType::AMPERSAND) {
+            let op: Token = self.view_prev().unwrap();
+            let right: Expr = self.parse_shift()?;
+            expr = Expr::Binary {
+                op,
+                left: Box::new(expr),
+                right: Box::new(right),
+            };
+        }
+        Ok(expr)
+    }
+
+    fn parse_shift(&mut self) -> Result<Expr, ParserError> {
+        let mut expr: Expr = self.parse_term()?;
+        while self.match_token(TokenType::LESS_LESS) || self.match_token(TokenType::GREATER_GREATER) {
+            let op: Token = self.view_prev().unwrap();
+            let right: Expr = self.parse_term()?;
+            expr = Expr::Binary {
+                op,
+                left: Box::new(expr),
+                right: Box::new(right),
+            };
+        }
+        Ok(expr)
+    }
+
     fn parse_term(&mut self) -> Result<Expr, ParserError> {
         let mut expr: Expr = self.parse_factor()?;
 
@@ -537,7 +1047,11 @@ impl Parser {
 
     fn parse_factor(&mut self) -> Result<Expr, ParserError> {
         let mut expr: Expr = self.parse_unary()?;
-        while self.match_token(TokenType::STAR) || self.match_token(TokenType::SLASH) {
+        while self.match_token(TokenType::STAR)
+            || self.match_token(TokenType::SLASH)
+            || self.match_token(TokenType::TILDE_SLASH)
+            || self.match_token(TokenType::PERCENT)
+        {
             let op: Token = self.view_prev().unwrap();
             let right: Expr = self.parse_unary()?;
             expr = Expr::Binary {
@@ -550,7 +1064,12 @@ impl Parser {
     }
 
     fn parse_unary(&mut self) -> Result<Expr, ParserError> {
-        if self.match_token(TokenType::BANG) || self.match_token(TokenType::MINUS) {
+        if let Some(op) = self.match_increment() {
+            let target: Expr = self.parse_unary()?;
+            let one = one(&op);
+            return update(target, op, one, false);
+        }
+        if self.match_token(TokenType::BANG) || self.match_token(TokenType::MINUS) || self.match_token(TokenType::TILDE) {
             let op: Token = self.view_prev().unwrap();
             let right: Expr = self.parse_unary()?;
             return Ok(Expr::Unary {
@@ -558,14 +1077,62 @@ impl Parser {
                 value: Box::new(right),
             });
         }
-        return self.parse_call();
+        return self.parse_power();
+    }
+
+    // binds tighter than a unary operator on its left, so `-2 ** 2` is -4, and
+    // groups to the right, so `2 ** 3 ** 2` is `2 ** 9`
+    fn parse_power(&mut self) -> Result<Expr, ParserError> {
+        let expr: Expr = self.parse_call()?;
+        if self.match_token(TokenType::STAR_STAR) {
+            let op: Token = self.view_prev().unwrap();
+            let right: Expr = self.parse_unary()?;
+            return Ok(Expr::Binary {
+                op,
+                left: Box::new(expr),
+                right: Box::new(right),
+            });
+        }
+        Ok(expr)
     }
 
     fn parse_call(&mut self) -> Result<Expr, ParserError> {
+        let expr: Expr = self.parse_call_chain()?;
+        if let Some(op) = self.match_increment() {
+            let one = one(&op);
+            return update(expr, op, one, true);
+        }
+        Ok(expr)
+    }
+
+    fn parse_call_chain(&mut self) -> Result<Expr, ParserError> {
         let mut expr: Expr = self.parse_expr_primary()?;
         loop {
             if self.match_token(TokenType::LEFT_PAREN) {
                 expr = self.finish_call(expr)?;
+            } else if self.match_token(TokenType::DOT) {
+                let name = self.consume(
+                    TokenType::IDENTIFIER,
+                    "Expect property name after '.'.".to_string(),
+                )?;
+                expr = Expr::Get {
+                    object: Box::new(expr),
+                    name,
+                };
+            } else if self.match_token(TokenType::LEFT_BRACKET) {
+                let bracket = self.view_prev().unwrap();
+                let index = self.parse_expr()?;
+                self.consume(
+                    TokenType::RIGHT_BRACKET,
+                    "Expect ']' after index.".to_string(),
+                )?;
+                let span = self.span_from(expr.span());
+                expr = Expr::Index {
+                    object: Box::new(expr),
+                    bracket,
+                    index: Box::new(index),
+                    span,
+                };
             } else {
                 break;
             }
@@ -578,9 +1145,8 @@ impl Parser {
         if !self.check_type(TokenType::RIGHT_PAREN) {
             loop {
                 if arguments.len() >= 255 {
-                    self.error_count += 1;
                     return Err(ParserError::FunctionError(
-                        self.view_prev().unwrap().line,
+                        self.view_prev().unwrap().span,
                         "Too many arguments".to_string(),
                     ));
                 }
@@ -601,3 +1167,42 @@ impl Parser {
         });
     }
 }
+
+// the made-up name a function expression is reported under
+fn lambda_name(start: &Token) -> Token {
+    let mut name = Token::new(TokenType::IDENTIFIER, "lambda".to_string(), start.line);
+    name.span = start.span;
+    name
+}
+
+// an update of `target`, which has to be something that can be assigned to
+fn update(target: Expr, op: Token, value: Expr, postfix: bool) -> Result<Expr, ParserError> {
+    if !matches!(target, Expr::Variable { .. } | Expr::Get { .. } | Expr::Index { .. }) {
+        return Err(ParserError::UnexpectedToken(op.span, "Invalid assignment target".to_string()));
+    }
+    let span = if postfix {
+        target.span().to(&op.span)
+    } else {
+        let start = if op.span.start < target.span().start { op.span } else { target.span() };
+        start.to(&op.span).to(&target.span()).to(&value.span())
+    };
+    Ok(Expr::Update {
+        target: Box::new(target),
+        op,
+        value: Box::new(value),
+        postfix,
+        span,
+    })
+}
+
+// Text the lexer resumed after the `}` closing a `${`, which it starts the token at.
+fn is_interpolation_tail(token: &Token) -> bool {
+    matches!(token.token_type, TokenType::STRING | TokenType::INTERPOLATION) && token.lexeme.starts_with('}')
+}
+
+// the amount `++` and `--` change their target by
+fn one(op: &Token) -> Expr {
+    let mut value = Token::new(TokenType::NUMBER, "1".to_string(), op.line);
+    value.span = op.span;
+    Expr::Literal { value }
+}
@@ -0,0 +1,441 @@
+use crate::ast::stmt::Stmt;
+use crate::diagnostic::Diagnostic;
+use crate::interpreter::Interpreter;
+use crate::lexer::Lexer;
+use crate::object::Object;
+use crate::parser::Parser;
+use crate::resolver::Resolver;
+use crate::token::{Token, TokenType};
+use std::env;
+use std::fs::{self, OpenOptions};
+use std::io::{self, BufRead, IsTerminal, Read, Write};
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+
+const PROMPT: &str = ">> ";
+const CONTINUATION_PROMPT: &str = ".. ";
+const HISTORY_FILE: &str = ".salmon_history";
+const HISTORY_LIMIT: usize = 1000;
+
+// One interpreter lives for the whole session, so definitions persist between inputs.
+pub struct Repl {
+    interpreter: Interpreter,
+    editor: LineEditor,
+}
+
+impl Repl {
+    pub fn new() -> Repl {
+        Repl {
+            interpreter: Interpreter::new(),
+            editor: LineEditor::new(),
+        }
+    }
+
+    pub fn set_max_call_depth(&mut self, depth: usize) {
+        self.interpreter.set_max_call_depth(depth);
+    }
+
+    pub fn run(&mut self) {
+        println!("salmon repl -- :quit or Ctrl-D to exit, :history to list past input");
+        loop {
+            let source = match self.read_input() {
+                Some(source) => source,
+                None => break,
+            };
+            match source.trim() {
+                "" => continue,
+                ":quit" | ":exit" => break,
+                ":history" => {
+                    for (i, entry) in self.editor.history.iter().enumerate() {
+                        println!("{:>4}  {}", i + 1, entry);
+                    }
+                    continue;
+                }
+                _ => self.eval(&source),
+            }
+        }
+    }
+
+    // keeps reading lines until every bracket, string and `${` opened so far has been closed
+    fn read_input(&mut self) -> Option<String> {
+        let mut source = String::new();
+        let mut prompt = PROMPT;
+        loop {
+            let line = self.editor.read_line(prompt)?;
+            source.push_str(&line);
+            source.push('\n');
+            if !is_incomplete(&source) {
+                return Some(source);
+            }
+            prompt = CONTINUATION_PROMPT;
+        }
+    }
+
+    fn eval(&mut self, source: &str) {
+        let file = self.interpreter.sources.add("<repl>", source);
+        let mut lexer = Lexer::with_file(source, file, "<repl>");
+        lexer.tokenize();
+        if lexer.num_errors > 0 {
+            return;
+        }
+        let tokens = lexer.get_tokens();
+
+        // a bare expression is evaluated and its value echoed back
+        let mut parser = Parser::new(tokens.clone());
+        if let Ok(expr) = parser.parse_expr() {
+            if parser.is_end() {
+                let stmts = vec![Stmt::Expression { span: expr.span(), expr: expr.clone() }];
+                if self.resolve(&stmts) {
+                    match self.interpreter.evaluate(&expr) {
+                        Ok(Object::Null) => {}
+                        Ok(value) => println!("{}", value),
+                        Err(e) => Diagnostic::from(&e).emit(&self.interpreter.sources),
+                    }
+                }
+                return;
+            }
+        }
+
+        let mut parser = Parser::new(with_trailing_semicolon(tokens));
+        let stmts = match parser.parse() {
+            Ok(stmts) => stmts,
+            Err(errors) => {
+                for e in &errors {
+                    Diagnostic::from(e).emit(&self.interpreter.sources);
+                }
+                return;
+            }
+        };
+        if self.resolve(&stmts) {
+            if let Err(e) = self.interpreter.interpret(&stmts) {
+                Diagnostic::from(&e).emit(&self.interpreter.sources);
+            }
+        }
+    }
+
+    fn resolve(&mut self, stmts: &Vec<Stmt>) -> bool {
+        match Resolver::new(&mut self.interpreter).resolve(stmts) {
+            Ok(()) => true,
+            Err(errors) => {
+                for e in &errors {
+                    Diagnostic::from(e).emit(&self.interpreter.sources);
+                }
+                false
+            }
+        }
+    }
+}
+
+// lets `var x = 1` or `print(x)` be typed without the final `;`
+fn with_trailing_semicolon(mut tokens: Vec<Token>) -> Vec<Token> {
+    let eof = tokens.pop().unwrap();
+    let needs_semicolon = match tokens.last() {
+        Some(last) => last.token_type != TokenType::SEMICOLON && last.token_type != TokenType::RIGHT_BRACE,
+        None => false,
+    };
+    if needs_semicolon {
+        tokens.push(Token::new(TokenType::SEMICOLON, ";".to_string(), eof.line));
+    }
+    tokens.push(eof);
+    tokens
+}
+
+// Lexes `source` to see whether it stops partway through: an unclosed bracket, a
+// string that runs off the end, or a `${` still open inside one.
+fn is_incomplete(source: &str) -> bool {
+    let mut lexer = Lexer::new(source).quiet();
+    let depth: i32 = lexer
+        .tokenize()
+        .iter()
+        .map(|token| match token.token_type {
+            TokenType::LEFT_PAREN | TokenType::LEFT_BRACE | TokenType::LEFT_BRACKET => 1,
+            TokenType::RIGHT_PAREN | TokenType::RIGHT_BRACE | TokenType::RIGHT_BRACKET => -1,
+            _ => 0,
+        })
+        .sum();
+    depth > 0 || lexer.is_incomplete()
+}
+
+// Minimal line editor: cursor movement, history recall and a history file.
+// Raw terminal input is switched on through `stty`, so no extra crates are needed;
+// when stdin is not a terminal it falls back to plain buffered reads.
+struct LineEditor {
+    history: Vec<String>,
+    history_path: Option<PathBuf>,
+    interactive: bool,
+}
+
+enum Key {
+    Char(char),
+    Enter,
+    Backspace,
+    Delete,
+    Left,
+    Right,
+    Up,
+    Down,
+    Home,
+    End,
+    ClearLine,
+    Interrupt,
+    Eof,
+    // stdin reached its end or failed, so no more keys will come
+    Closed,
+    Ignored,
+}
+
+impl LineEditor {
+    fn new() -> LineEditor {
+        let history_path = env::var_os("HOME").map(|home| PathBuf::from(home).join(HISTORY_FILE));
+        let mut history: Vec<String> = match &history_path {
+            Some(path) => fs::read_to_string(path)
+                .map(|contents| contents.lines().map(str::to_string).collect())
+                .unwrap_or_default(),
+            None => Vec::new(),
+        };
+        // a file written before it was capped may hold more than the limit
+        history.drain(..history.len().saturating_sub(HISTORY_LIMIT));
+        LineEditor {
+            history,
+            history_path,
+            interactive: io::stdin().is_terminal() && io::stdout().is_terminal(),
+        }
+    }
+
+    fn read_line(&mut self, prompt: &str) -> Option<String> {
+        let line = if self.interactive {
+            match RawMode::enable() {
+                Some(raw) => {
+                    let line = self.edit_line(prompt);
+                    drop(raw);
+                    line
+                }
+                None => self.read_plain(prompt),
+            }
+        } else {
+            self.read_plain(prompt)
+        }?;
+
+        if !line.trim().is_empty() && self.history.last() != Some(&line) {
+            self.add_history(&line);
+        }
+        Some(line)
+    }
+
+    fn read_plain(&mut self, prompt: &str) -> Option<String> {
+        print!("{}", prompt);
+        io::stdout().flush().ok();
+        let mut line = String::new();
+        match io::stdin().lock().read_line(&mut line) {
+            Ok(0) | Err(_) => None,
+            Ok(_) => Some(line.trim_end_matches(['\n', '\r']).to_string()),
+        }
+    }
+
+    fn edit_line(&mut self, prompt: &str) -> Option<String> {
+        let mut buffer: Vec<char> = Vec::new();
+        let mut cursor = 0;
+        // index into history while browsing; `history.len()` is the line being typed
+        let mut recall = self.history.len();
+        let mut draft: Vec<char> = Vec::new();
+
+        self.redraw(prompt, &buffer, cursor);
+        loop {
+            match read_key() {
+                Key::Char(c) => {
+                    buffer.insert(cursor, c);
+                    cursor += 1;
+                }
+                Key::Enter => {
+                    print!("\r\n");
+                    io::stdout().flush().ok();
+                    return Some(buffer.into_iter().collect());
+                }
+                Key::Backspace if cursor > 0 => {
+                    cursor -= 1;
+                    buffer.remove(cursor);
+                }
+                Key::Delete if cursor < buffer.len() => {
+                    buffer.remove(cursor);
+                }
+                Key::Left if cursor > 0 => cursor -= 1,
+                Key::Right if cursor < buffer.len() => cursor += 1,
+                Key::Home => cursor = 0,
+                Key::End => cursor = buffer.len(),
+                Key::ClearLine => {
+                    buffer.clear();
+                    cursor = 0;
+                }
+                Key::Up if recall > 0 => {
+                    if recall == self.history.len() {
+                        draft = buffer.clone();
+                    }
+                    recall -= 1;
+                    buffer = self.history[recall].chars().collect();
+                    cursor = buffer.len();
+                }
+                Key::Down if recall < self.history.len() => {
+                    recall += 1;
+                    buffer = if recall == self.history.len() {
+                        draft.clone()
+                    } else {
+                        self.history[recall].chars().collect()
+                    };
+                    cursor = buffer.len();
+                }
+                Key::Interrupt => {
+                    print!("^C\r\n");
+                    buffer.clear();
+                    cursor = 0;
+                    recall = self.history.len();
+                }
+                Key::Eof if buffer.is_empty() => {
+                    print!("\r\n");
+                    io::stdout().flush().ok();
+                    return None;
+                }
+                // submit what was typed; the next read finds stdin closed again and ends the session
+                Key::Closed => {
+                    print!("\r\n");
+                    io::stdout().flush().ok();
+                    if buffer.is_empty() {
+                        return None;
+                    }
+                    return Some(buffer.into_iter().collect());
+                }
+                _ => {}
+            }
+            self.redraw(prompt, &buffer, cursor);
+        }
+    }
+
+    fn redraw(&self, prompt: &str, buffer: &[char], cursor: usize) {
+        let line: String = buffer.iter().collect();
+        print!("\r\x1b[K{}{}", prompt, line);
+        let back = buffer.len() - cursor;
+        if back > 0 {
+            print!("\x1b[{}D", back);
+        }
+        io::stdout().flush().ok();
+    }
+
+    // Appends to the history file until it holds `HISTORY_LIMIT` entries, then
+    // rewrites it with just the entries kept in memory.
+    fn add_history(&mut self, line: &str) {
+        self.history.push(line.to_string());
+        let trimmed = self.history.len() > HISTORY_LIMIT;
+        if trimmed {
+            self.history.remove(0);
+        }
+        if let Some(path) = &self.history_path {
+            if trimmed {
+                let mut contents = self.history.join("\n");
+                contents.push('\n');
+                fs::write(path, contents).ok();
+            } else if let Ok(mut file) = OpenOptions::new().create(true).append(true).open(path) {
+                writeln!(file, "{}", line).ok();
+            }
+        }
+    }
+}
+
+fn read_byte() -> Option<u8> {
+    let mut byte = [0u8; 1];
+    match io::stdin().lock().read(&mut byte) {
+        Ok(1) => Some(byte[0]),
+        _ => None,
+    }
+}
+
+fn read_key() -> Key {
+    let byte = match read_byte() {
+        Some(byte) => byte,
+        None => return Key::Closed,
+    };
+    match byte {
+        b'\r' | b'\n' => Key::Enter,
+        0x7f | 0x08 => Key::Backspace,
+        0x01 => Key::Home,
+        0x03 => Key::Interrupt,
+        0x04 => Key::Eof,
+        0x05 => Key::End,
+        0x15 => Key::ClearLine,
+        0x1b => read_escape(),
+        byte if byte < 0x20 => Key::Ignored,
+        byte => read_utf8(byte),
+    }
+}
+
+// arrow, home/end and delete keys arrive as `ESC [ ...` sequences
+fn read_escape() -> Key {
+    if read_byte() != Some(b'[') {
+        return Key::Ignored;
+    }
+    match read_byte() {
+        Some(b'A') => Key::Up,
+        Some(b'B') => Key::Down,
+        Some(b'C') => Key::Right,
+        Some(b'D') => Key::Left,
+        Some(b'H') => Key::Home,
+        Some(b'F') => Key::End,
+        Some(b'3') => {
+            read_byte();
+            Key::Delete
+        }
+        _ => Key::Ignored,
+    }
+}
+
+fn read_utf8(first: u8) -> Key {
+    let width = match first {
+        0xc0..=0xdf => 2,
+        0xe0..=0xef => 3,
+        0xf0..=0xf7 => 4,
+        _ => 1,
+    };
+    let mut bytes = vec![first];
+    for _ in 1..width {
+        match read_byte() {
+            Some(byte) => bytes.push(byte),
+            None => return Key::Closed,
+        }
+    }
+    match std::str::from_utf8(&bytes).ok().and_then(|s| s.chars().next()) {
+        Some(c) => Key::Char(c),
+        None => Key::Ignored,
+    }
+}
+
+// Puts the terminal into unbuffered, no-echo mode and restores the saved settings on drop.
+struct RawMode {
+    saved: String,
+}
+
+impl RawMode {
+    fn enable() -> Option<RawMode> {
+        let saved = stty(&["-g"])?;
+        stty(&["-icanon", "-echo", "-isig", "min", "1"])?;
+        Some(RawMode {
+            saved: saved.trim().to_string(),
+        })
+    }
+}
+
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        stty(&[self.saved.as_str()]);
+    }
+}
+
+fn stty(args: &[&str]) -> Option<String> {
+    let output = Command::new("stty")
+        .args(args)
+        .stdin(Stdio::inherit())
+        .output()
+        .ok()?;
+    if output.status.success() {
+        String::from_utf8(output.stdout).ok()
+    } else {
+        None
+    }
+}
@@ -0,0 +1,378 @@
+use crate::ast::expr;
+use crate::ast::expr::Expr;
+use crate::ast::stmt;
+use crate::ast::stmt::Stmt;
+use crate::error::ParserError;
+use crate::interpreter::Interpreter;
+use crate::token::Token;
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum FunctionType {
+    None,
+    Function,
+    Method,
+    Initializer,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ClassType {
+    None,
+    Class,
+    Subclass,
+}
+
+// Static pass run between parsing and interpreting. Every local variable
+// reference gets its scope distance recorded on the interpreter; anything
+// left unresolved is looked up in globals. Without an interpreter it only
+// checks the program, which is all the VM needs.
+pub struct Resolver<'a> {
+    interpreter: Option<&'a mut Interpreter>,
+    scopes: Vec<HashMap<String, bool>>,
+    current_function: FunctionType,
+    current_class: ClassType,
+    errors: Vec<ParserError>,
+}
+
+impl<'a> Resolver<'a> {
+    pub fn new(interpreter: &'a mut Interpreter) -> Resolver<'a> {
+        Resolver {
+            interpreter: Some(interpreter),
+            scopes: Vec::new(),
+            current_function: FunctionType::None,
+            current_class: ClassType::None,
+            errors: Vec::new(),
+        }
+    }
+
+    pub fn standalone() -> Resolver<'a> {
+        Resolver {
+            interpreter: None,
+            scopes: Vec::new(),
+            current_function: FunctionType::None,
+            current_class: ClassType::None,
+            errors: Vec::new(),
+        }
+    }
+
+    pub fn resolve(&mut self, stmts: &Vec<Stmt>) -> Result<(), Vec<ParserError>> {
+        self.resolve_stmts(stmts);
+        if self.errors.is_empty() {
+            Ok(())
+        } else {
+            Err(std::mem::take(&mut self.errors))
+        }
+    }
+
+    fn resolve_stmts(&mut self, stmts: &Vec<Stmt>) {
+        for stmt in stmts {
+            stmt.accept(self);
+        }
+    }
+
+    fn resolve_expr(&mut self, expr: &Expr) {
+        expr.accept(self);
+    }
+
+    fn resolve_function(&mut self, params: &Vec<Token>, body: &Vec<Stmt>, function_type: FunctionType) {
+        let enclosing_function = self.current_function;
+        self.current_function = function_type;
+
+        self.begin_scope();
+        for param in params {
+            self.declare(param);
+            self.define(param);
+        }
+        self.resolve_stmts(body);
+        self.end_scope();
+
+        self.current_function = enclosing_function;
+    }
+
+    fn resolve_local(&mut self, id: usize, name: &str) {
+        for (depth, scope) in self.scopes.iter().rev().enumerate() {
+            if scope.contains_key(name) {
+                if let Some(interpreter) = self.interpreter.as_mut() {
+                    interpreter.resolve(id, depth);
+                }
+                return;
+            }
+        }
+    }
+
+    fn begin_scope(&mut self) {
+        self.scopes.push(HashMap::new());
+    }
+
+    fn end_scope(&mut self) {
+        self.scopes.pop();
+    }
+
+    fn declare(&mut self, name: &Token) {
+        let already_declared = match self.scopes.last() {
+            Some(scope) => scope.contains_key(&name.lexeme),
+            None => return,
+        };
+        if already_declared {
+            self.error(name, "Already a variable with this name in this scope.");
+        }
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.insert(name.lexeme.clone(), false);
+        }
+    }
+
+    fn define(&mut self, name: &Token) {
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.insert(name.lexeme.clone(), true);
+        }
+    }
+
+    fn error(&mut self, token: &Token, message: &str) {
+        self.errors.push(ParserError::ResolutionError(
+            token.span,
+            message.to_string(),
+        ));
+    }
+}
+
+impl<'a> expr::Visitor<()> for Resolver<'a> {
+    fn visit_unary(&mut self, op: &Token, value: &Box<Expr>) {
+        self.resolve_expr(value);
+    }
+
+    fn visit_binary(&mut self, op: &Token, left: &Box<Expr>, right: &Box<Expr>) {
+        self.resolve_expr(left);
+        self.resolve_expr(right);
+    }
+
+    fn visit_grouping(&mut self, expr: &Box<Expr>) {
+        self.resolve_expr(expr);
+    }
+
+    fn visit_literal(&mut self, value: &Token) {}
+
+    fn visit_variable(&mut self, name: &Token, id: usize) {
+        if let Some(scope) = self.scopes.last() {
+            if scope.get(&name.lexeme) == Some(&false) {
+                self.error(name, "Can't read local variable in its own initializer.");
+            }
+        }
+        self.resolve_local(id, &name.lexeme);
+    }
+
+    fn visit_assign(&mut self, name: &Token, value: &Box<Expr>, id: usize) {
+        self.resolve_expr(value);
+        self.resolve_local(id, &name.lexeme);
+    }
+
+    fn visit_call(&mut self, callee: &Box<Expr>, paren: &Token, arguments: &Vec<Expr>) {
+        self.resolve_expr(callee);
+        for arg in arguments {
+            self.resolve_expr(arg);
+        }
+    }
+
+    fn visit_this(&mut self, keyword: &Token, id: usize) {
+        if self.current_class == ClassType::None {
+            self.error(keyword, "Can't use 'this' outside of a class.");
+            return;
+        }
+        self.resolve_local(id, "this");
+    }
+
+    fn visit_super(&mut self, keyword: &Token, method: &Token, id: usize) {
+        match self.current_class {
+            ClassType::None => self.error(keyword, "Can't use 'super' outside of a class."),
+            ClassType::Class => self.error(keyword, "Can't use 'super' in a class with no superclass."),
+            ClassType::Subclass => self.resolve_local(id, "super"),
+        }
+    }
+
+    fn visit_logical(&mut self, op: &Token, left: &Box<Expr>, right: &Box<Expr>) {
+        self.resolve_expr(left);
+        self.resolve_expr(right);
+    }
+
+    fn visit_set(&mut self, object: &Box<Expr>, name: &Token, value: &Box<Expr>) {
+        self.resolve_expr(value);
+        self.resolve_expr(object);
+    }
+
+    fn visit_get(&mut self, object: &Box<Expr>, name: &Token) {
+        self.resolve_expr(object);
+    }
+
+    fn visit_list(&mut self, bracket: &Token, elements: &Vec<Expr>) {
+        for element in elements {
+            self.resolve_expr(element);
+        }
+    }
+
+    fn visit_map(&mut self, brace: &Token, entries: &Vec<(Expr, Expr)>) {
+        for (key, value) in entries {
+            self.resolve_expr(key);
+            self.resolve_expr(value);
+        }
+    }
+
+    fn visit_index(&mut self, object: &Box<Expr>, bracket: &Token, index: &Box<Expr>) {
+        self.resolve_expr(object);
+        self.resolve_expr(index);
+    }
+
+    fn visit_set_index(&mut self, object: &Box<Expr>, bracket: &Token, index: &Box<Expr>, value: &Box<Expr>) {
+        self.resolve_expr(value);
+        self.resolve_expr(object);
+        self.resolve_expr(index);
+    }
+
+    fn visit_interpolation(&mut self, start: &Token, parts: &Vec<Expr>) {
+        for part in parts {
+            self.resolve_expr(part);
+        }
+    }
+
+    fn visit_lambda(&mut self, name: &Token, params: &Vec<Token>, body: &Vec<Stmt>) {
+        self.resolve_function(params, body, FunctionType::Function);
+    }
+
+    fn visit_update(&mut self, target: &Box<Expr>, op: &Token, value: &Box<Expr>, postfix: bool) {
+        self.resolve_expr(value);
+        self.resolve_expr(target);
+    }
+}
+
+impl<'a> stmt::Visitor<()> for Resolver<'a> {
+    fn visit_block(&mut self, stmts: &Vec<Stmt>) {
+        self.begin_scope();
+        self.resolve_stmts(stmts);
+        self.end_scope();
+    }
+
+    fn visit_break(&mut self, keyword: &Token) {}
+
+    fn visit_continue(&mut self, keyword: &Token) {}
+
+    fn visit_expression(&mut self, expr: &Expr) {
+        self.resolve_expr(expr);
+    }
+
+    fn visit_class(&mut self, name: &Token, superclass: &Option<Expr>, methods: &Vec<Stmt>) {
+        let enclosing_class = self.current_class;
+        self.current_class = ClassType::Class;
+
+        self.declare(name);
+        self.define(name);
+
+        if let Some(superclass) = superclass {
+            self.current_class = ClassType::Subclass;
+            self.resolve_expr(superclass);
+            self.begin_scope();
+            if let Some(scope) = self.scopes.last_mut() {
+                scope.insert("super".to_string(), true);
+            }
+        }
+
+        self.begin_scope();
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.insert("this".to_string(), true);
+        }
+
+        for method in methods {
+            if let Stmt::Function { name, params, body, .. } = method {
+                let function_type = if name.lexeme == "init" {
+                    FunctionType::Initializer
+                } else {
+                    FunctionType::Method
+                };
+                self.resolve_function(params, body, function_type);
+            }
+        }
+
+        self.end_scope();
+        if superclass.is_some() {
+            self.end_scope();
+        }
+
+        self.current_class = enclosing_class;
+    }
+
+    fn visit_function(&mut self, name: &Token, params: &Vec<Token>, body: &Vec<Stmt>) {
+        // declared eagerly so the body can refer to itself recursively
+        self.declare(name);
+        self.define(name);
+        self.resolve_function(params, body, FunctionType::Function);
+    }
+
+    fn visit_import(&mut self, keyword: &Token, path: &Token, name: &Token) {
+        self.declare(name);
+        self.define(name);
+    }
+
+    fn visit_if(&mut self, condition: &Expr, then_branch: &Stmt, else_branch: &Option<Box<Stmt>>) {
+        self.resolve_expr(condition);
+        then_branch.accept(self);
+        if let Some(else_branch) = else_branch {
+            else_branch.accept(self);
+        }
+    }
+
+    fn visit_print(&mut self, expr: &Expr) {
+        self.resolve_expr(expr);
+    }
+
+    fn visit_return(&mut self, keyword: &Token, value: &Option<Expr>) {
+        if self.current_function == FunctionType::None {
+            self.error(keyword, "Can't return from top-level code.");
+        }
+        if let Some(value) = value {
+            if self.current_function == FunctionType::Initializer {
+                self.error(keyword, "Can't return a value from an initializer.");
+            }
+            self.resolve_expr(value);
+        }
+    }
+
+    fn visit_throw(&mut self, keyword: &Token, value: &Expr) {
+        self.resolve_expr(value);
+    }
+
+    fn visit_try(
+        &mut self,
+        body: &Vec<Stmt>,
+        catch_name: &Option<Token>,
+        catch_body: &Option<Vec<Stmt>>,
+        finally_body: &Option<Vec<Stmt>>,
+    ) {
+        self.visit_block(body);
+        if let Some(catch_body) = catch_body {
+            // the caught value shares a scope with the catch block's statements
+            self.begin_scope();
+            if let Some(name) = catch_name {
+                self.declare(name);
+                self.define(name);
+            }
+            self.resolve_stmts(catch_body);
+            self.end_scope();
+        }
+        if let Some(finally_body) = finally_body {
+            self.visit_block(finally_body);
+        }
+    }
+
+    fn visit_var(&mut self, name: &Token, initializer: &Option<Expr>) {
+        self.declare(name);
+        if let Some(initializer) = initializer {
+            self.resolve_expr(initializer);
+        }
+        self.define(name);
+    }
+
+    fn visit_while(&mut self, condition: &Expr, body: &Stmt, increment: &Option<Expr>, per_iteration: bool) {
+        self.resolve_expr(condition);
+        body.accept(self);
+        if let Some(increment) = increment {
+            self.resolve_expr(increment);
+        }
+    }
+}
@@ -50,8 +50,29 @@ impl Scope {
             
         }
     }
-    
-    
-    
-    
+
+    // `distance` comes from the resolver, so the variable is known to live exactly that many scopes up
+    pub fn get_at(&self, distance: usize, name: &Token) -> Result<Object, RuntimeError> {
+        if distance == 0 {
+            return match self.values.get(&name.lexeme) {
+                Some(value) => Ok((*value).clone()),
+                None => Err(RuntimeError::UndefinedVariable((*name).clone())),
+            };
+        }
+        match &self.parent {
+            Some(parent) => parent.borrow().get_at(distance - 1, name),
+            None => Err(RuntimeError::UndefinedVariable((*name).clone())),
+        }
+    }
+
+    pub fn assign_at(&mut self, distance: usize, name: &Token, value: Object) -> Result<(), RuntimeError> {
+        if distance == 0 {
+            self.values.insert(name.lexeme.clone(), value);
+            return Ok(());
+        }
+        match &self.parent {
+            Some(parent) => parent.borrow_mut().assign_at(distance - 1, name, value),
+            None => Err(RuntimeError::UndefinedVariable((*name).clone())),
+        }
+    }
 }
\ No newline at end of file
@@ -6,13 +6,27 @@ pub enum TokenType {
     RIGHT_PAREN,
     LEFT_BRACE,
     RIGHT_BRACE,
+    LEFT_BRACKET,
+    RIGHT_BRACKET,
     STAR,
+    STAR_STAR,
     DOT,
     COMMA,
     PLUS,
+    PLUS_EQUAL,
+    PLUS_PLUS,
     SLASH,
+    PERCENT,
+    AMPERSAND,
+    PIPE,
+    CARET,
+    TILDE,
+    TILDE_SLASH,
+    LESS_LESS,
+    GREATER_GREATER,
     EQUAL,
     EQUAL_EQUAL,
+    ARROW,
     BANG_EQUAL,
     GREATER,
     GREATER_EQUAL,
@@ -20,34 +34,82 @@ pub enum TokenType {
     LESS_EQUAL,
     BANG,
     SEMICOLON,
+    COLON,
     MINUS,
+    MINUS_EQUAL,
+    MINUS_MINUS,
+    STAR_EQUAL,
+    SLASH_EQUAL,
+    PERCENT_EQUAL,
     STRING,
+    // the text of a string up to a `${`
+    INTERPOLATION,
     NUMBER,
     AND,
+    BREAK,
     CLASS,
+    CONTINUE,
+    CATCH,
     ELSE,
     FALSE,
+    FINALLY,
     FOR,
     FN,
     IF,
+    IMPORT,
     NULL,
     OR,
     PRINT,
     RETURN,
     SUPER,
     THIS,
+    THROW,
     TRUE,
+    TRY,
     VAR,
     WHILE,
     IDENTIFIER,
     EOF,
 }
 
+// A region of the source: byte offsets `start..end` into the file numbered `file`,
+// plus the line and column (both 1-based, column counted in characters) where it begins.
+// Tokens made up by the parser have no source text and keep a zero-width span at column 0.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Span {
+    pub file: usize,
+    pub start: usize,
+    pub end: usize,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Span {
+    pub fn new(file: usize, start: usize, end: usize, line: usize, column: usize) -> Self {
+        Span { file, start, end, line, column }
+    }
+
+    // from the start of `self` to the end of `other`
+    pub fn to(&self, other: &Span) -> Span {
+        Span {
+            file: self.file,
+            start: self.start,
+            end: other.end.max(self.end),
+            line: self.line,
+            column: self.column,
+        }
+    }
+}
+
+// `lexeme` is the source text as written; a string literal's contents, with its
+// quotes dropped and escapes applied, are kept apart in `literal`.
 #[derive(Debug, Clone, PartialEq)]
 pub struct Token {
     pub token_type: TokenType,
     pub lexeme: String,
+    pub literal: Option<String>,
     pub line: usize,
+    pub span: Span,
 }
 
 
@@ -56,7 +118,19 @@ impl Token {
         Token {
             token_type,
             lexeme,
+            literal: None,
             line,
+            span: Span::new(0, 0, 0, line, 0),
         }
     }
+
+    pub fn with_literal(mut self, literal: String) -> Self {
+        self.literal = Some(literal);
+        self
+    }
+
+    // what the token stands for: the cooked value of a literal, otherwise its lexeme
+    pub fn value(&self) -> &str {
+        self.literal.as_deref().unwrap_or(&self.lexeme)
+    }
 }
@@ -0,0 +1,140 @@
+use crate::object::Object;
+use crate::token::Token;
+use std::fmt;
+use std::rc::Rc;
+
+// One VM instruction. Operands are indices into the chunk's constants or
+// tokens, stack slots, or absolute jump targets.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Op {
+    Constant(usize),
+    Null,
+    True,
+    False,
+    Pop,
+    // pushes a copy of the value `n` slots below the top
+    Peek(usize),
+    // overwrites the value `n` slots below the top with the top, leaving it in place
+    Poke(usize),
+
+    GetLocal(usize),
+    SetLocal(usize),
+    GetUpvalue(usize),
+    SetUpvalue(usize),
+    // globals are named by the instruction's token
+    DefineGlobal,
+    GetGlobal,
+    SetGlobal,
+
+    GetProperty,
+    CheckFieldTarget,
+    SetProperty,
+    // [this, superclass] -> the superclass method bound to `this`
+    GetSuper,
+    Index,
+    SetIndex,
+    List(usize),
+    // joins the top `n` values into one string
+    Interpolate(usize),
+    // checks the key just pushed, before its value is evaluated
+    CheckKey,
+    Map(usize),
+
+    // the instruction's token is the operator, which picks the operation
+    Unary,
+    Binary,
+
+    Print,
+    Jump(usize),
+    // pops the condition
+    JumpIfFalse(usize),
+
+    // `site` indexes the token covering the whole call; the instruction's token is the `(`
+    Call { argc: usize, site: usize },
+    // a call in `return` position, made in place of the current frame when possible
+    TailCall { argc: usize, site: usize },
+    Closure(usize),
+    CloseUpvalue,
+    // closes upvalues over the slots from `n` up, leaving the values on the stack
+    CloseUpvalues(usize),
+    Return,
+
+    CheckSuperclass,
+    // [superclass?, method closures...] -> class
+    Class { methods: usize, has_superclass: bool },
+    // `path` indexes the token holding the module path
+    Import(usize),
+
+    Throw,
+    // errors raised before the matching `PopHandler` jump to `target`, with the
+    // error object pushed for a catch, or held back for a finally to rethrow
+    PushCatch(usize),
+    PushFinally(usize),
+    PopHandler,
+    // drops or re-raises the error a finally block is running for
+    PopPending,
+    Rethrow,
+}
+
+#[derive(Debug, Clone)]
+pub enum Constant {
+    Value(Object),
+    Function(Rc<FunctionProto>),
+}
+
+#[derive(Debug, Default)]
+pub struct Chunk {
+    pub code: Vec<Op>,
+    pub constants: Vec<Constant>,
+    // the source token behind each instruction, for error reporting
+    pub tokens: Vec<Token>,
+    locations: Vec<usize>,
+}
+
+impl Chunk {
+    pub fn new() -> Chunk {
+        Chunk::default()
+    }
+
+    pub fn write(&mut self, op: Op, token: &Token) -> usize {
+        let location = match self.locations.last() {
+            Some(&last) if self.tokens[last].span == token.span && self.tokens[last].lexeme == token.lexeme => last,
+            _ => self.add_token(token),
+        };
+        self.code.push(op);
+        self.locations.push(location);
+        self.code.len() - 1
+    }
+
+    pub fn add_token(&mut self, token: &Token) -> usize {
+        self.tokens.push(token.clone());
+        self.tokens.len() - 1
+    }
+
+    pub fn add_constant(&mut self, constant: Constant) -> usize {
+        self.constants.push(constant);
+        self.constants.len() - 1
+    }
+
+    pub fn token_at(&self, offset: usize) -> &Token {
+        &self.tokens[self.locations[offset]]
+    }
+}
+
+// A compiled function body, shared by every closure created from it.
+#[derive(Debug)]
+pub struct FunctionProto {
+    pub name: Token,
+    pub arity: usize,
+    pub chunk: Chunk,
+    // for each upvalue: whether it captures a local of the enclosing function
+    // (by slot) or one of the enclosing function's own upvalues (by index)
+    pub upvalues: Vec<(bool, usize)>,
+    pub is_initializer: bool,
+}
+
+impl fmt::Display for FunctionProto {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "<fn {}>", self.name.lexeme)
+    }
+}
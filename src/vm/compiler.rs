@@ -0,0 +1,743 @@
+use super::chunk::{Chunk, Constant, FunctionProto, Op};
+use crate::ast::expr;
+use crate::ast::expr::Expr;
+use crate::ast::stmt;
+use crate::ast::stmt::Stmt;
+use crate::ops;
+use crate::token::{Token, TokenType};
+use std::mem;
+use std::rc::Rc;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum FunctionKind {
+    Script,
+    Function,
+    Method,
+    Initializer,
+}
+
+struct Local {
+    // empty for slots no name can refer to
+    name: String,
+    depth: usize,
+    captured: bool,
+}
+
+// What a `break`, `continue` or `return` has to clean up on its way out.
+enum Context {
+    Loop {
+        locals: usize,
+        breaks: Vec<usize>,
+        continues: Vec<usize>,
+    },
+    // a catch or finally handler is installed
+    Handler,
+    // leaving the try (or its catch) runs the finally block first
+    Finally { body: Vec<Stmt>, locals: usize },
+    // inside a finally block that is running because of an error
+    Pending,
+}
+
+struct FunctionState {
+    name: Token,
+    kind: FunctionKind,
+    arity: usize,
+    chunk: Chunk,
+    locals: Vec<Local>,
+    upvalues: Vec<(bool, usize)>,
+    scope_depth: usize,
+    contexts: Vec<Context>,
+}
+
+impl FunctionState {
+    fn new(name: Token, kind: FunctionKind) -> FunctionState {
+        // slot 0 holds the receiver in methods and the callee everywhere else
+        let receiver = match kind {
+            FunctionKind::Method | FunctionKind::Initializer => "this",
+            FunctionKind::Script | FunctionKind::Function => "",
+        };
+        FunctionState {
+            name,
+            kind,
+            arity: 0,
+            chunk: Chunk::new(),
+            locals: vec![Local {
+                name: receiver.to_string(),
+                depth: 0,
+                captured: false,
+            }],
+            upvalues: Vec::new(),
+            scope_depth: 0,
+            contexts: Vec::new(),
+        }
+    }
+
+    fn resolve_local(&self, name: &str) -> Option<usize> {
+        self.locals.iter().rposition(|local| !local.name.is_empty() && local.name == name)
+    }
+}
+
+// Compiles resolved statements into bytecode for the VM. Variables follow the
+// same rules as the resolver: locals live in stack slots, variables of enclosing
+// functions are captured as upvalues, and anything else is a global.
+pub struct Compiler {
+    states: Vec<FunctionState>,
+}
+
+impl Compiler {
+    pub fn compile(stmts: &Vec<Stmt>) -> Rc<FunctionProto> {
+        let name = Token::new(TokenType::IDENTIFIER, "<script>".to_string(), 0);
+        let mut compiler = Compiler {
+            states: vec![FunctionState::new(name, FunctionKind::Script)],
+        };
+        for stmt in stmts {
+            stmt.accept(&mut compiler);
+        }
+        compiler.emit_op(Op::Null);
+        compiler.emit_op(Op::Return);
+        let state = compiler.states.pop().unwrap();
+        Rc::new(FunctionProto {
+            name: state.name,
+            arity: 0,
+            chunk: state.chunk,
+            upvalues: state.upvalues,
+            is_initializer: false,
+        })
+    }
+
+    fn state(&mut self) -> &mut FunctionState {
+        self.states.last_mut().unwrap()
+    }
+
+    fn emit(&mut self, op: Op, token: &Token) -> usize {
+        self.state().chunk.write(op, token)
+    }
+
+    // for instructions that cannot fail, reported against the previous instruction's token
+    fn emit_op(&mut self, op: Op) -> usize {
+        let chunk = &mut self.state().chunk;
+        let token = match chunk.code.len() {
+            0 => Token::new(TokenType::EOF, "".to_string(), 0),
+            len => chunk.token_at(len - 1).clone(),
+        };
+        chunk.write(op, &token)
+    }
+
+    fn here(&mut self) -> usize {
+        self.state().chunk.code.len()
+    }
+
+    fn patch(&mut self, at: usize) {
+        let target = self.here();
+        let op = match self.state().chunk.code[at] {
+            Op::Jump(_) => Op::Jump(target),
+            Op::JumpIfFalse(_) => Op::JumpIfFalse(target),
+            Op::PushCatch(_) => Op::PushCatch(target),
+            Op::PushFinally(_) => Op::PushFinally(target),
+            op => unreachable!("{:?} has no jump target", op),
+        };
+        self.state().chunk.code[at] = op;
+    }
+
+    fn compile_expr(&mut self, expr: &Expr) {
+        expr.accept(self);
+    }
+
+    fn compile_block(&mut self, stmts: &Vec<Stmt>) {
+        self.begin_scope();
+        for stmt in stmts {
+            stmt.accept(self);
+        }
+        self.end_scope();
+    }
+
+    fn begin_scope(&mut self) {
+        self.state().scope_depth += 1;
+    }
+
+    fn end_scope(&mut self) {
+        let state = self.state();
+        state.scope_depth -= 1;
+        let depth = state.scope_depth;
+        while let Some(local) = self.state().locals.last() {
+            if local.depth <= depth {
+                break;
+            }
+            let op = if local.captured { Op::CloseUpvalue } else { Op::Pop };
+            self.state().locals.pop();
+            self.emit_op(op);
+        }
+    }
+
+    // drops the locals above `count` from the stack, leaving the compiler's view untouched
+    fn discard_locals(&mut self, count: usize) {
+        let ops: Vec<Op> = self.state().locals[count..]
+            .iter()
+            .rev()
+            .map(|local| if local.captured { Op::CloseUpvalue } else { Op::Pop })
+            .collect();
+        for op in ops {
+            self.emit_op(op);
+        }
+    }
+
+    fn add_local(&mut self, name: &str) {
+        let depth = self.state().scope_depth;
+        self.state().locals.push(Local {
+            name: name.to_string(),
+            depth,
+            captured: false,
+        });
+    }
+
+    // top-level names are globals; everything else takes the stack slot its value is already in
+    fn declare_variable(&mut self, name: &Token) {
+        if self.state().scope_depth > 0 {
+            self.add_local(&name.lexeme);
+        }
+    }
+
+    fn define_variable(&mut self, name: &Token) {
+        if self.state().scope_depth == 0 {
+            self.emit(Op::DefineGlobal, name);
+        }
+    }
+
+    fn resolve_upvalue(&mut self, state: usize, name: &str) -> Option<usize> {
+        if state == 0 {
+            return None;
+        }
+        if let Some(slot) = self.states[state - 1].resolve_local(name) {
+            self.states[state - 1].locals[slot].captured = true;
+            return Some(self.add_upvalue(state, true, slot));
+        }
+        let index = self.resolve_upvalue(state - 1, name)?;
+        Some(self.add_upvalue(state, false, index))
+    }
+
+    fn add_upvalue(&mut self, state: usize, is_local: bool, index: usize) -> usize {
+        let upvalues = &mut self.states[state].upvalues;
+        if let Some(existing) = upvalues.iter().position(|&upvalue| upvalue == (is_local, index)) {
+            return existing;
+        }
+        upvalues.push((is_local, index));
+        upvalues.len() - 1
+    }
+
+    fn get_variable(&mut self, name: &Token) {
+        let current = self.states.len() - 1;
+        if let Some(slot) = self.states[current].resolve_local(&name.lexeme) {
+            self.emit(Op::GetLocal(slot), name);
+        } else if let Some(index) = self.resolve_upvalue(current, &name.lexeme) {
+            self.emit(Op::GetUpvalue(index), name);
+        } else {
+            self.emit(Op::GetGlobal, name);
+        }
+    }
+
+    fn set_variable(&mut self, name: &Token) {
+        let current = self.states.len() - 1;
+        if let Some(slot) = self.states[current].resolve_local(&name.lexeme) {
+            self.emit(Op::SetLocal(slot), name);
+        } else if let Some(index) = self.resolve_upvalue(current, &name.lexeme) {
+            self.emit(Op::SetUpvalue(index), name);
+        } else {
+            self.emit(Op::SetGlobal, name);
+        }
+    }
+
+    // leaves a closure for the function on the stack
+    fn compile_function(&mut self, name: &Token, params: &Vec<Token>, body: &Vec<Stmt>, kind: FunctionKind) {
+        self.states.push(FunctionState::new(name.clone(), kind));
+        self.state().arity = params.len();
+        self.begin_scope();
+        for param in params {
+            self.add_local(&param.lexeme);
+        }
+        for stmt in body {
+            stmt.accept(self);
+        }
+        self.emit(Op::Null, name);
+        self.emit_op(Op::Return);
+
+        let state = self.states.pop().unwrap();
+        let function = Rc::new(FunctionProto {
+            name: state.name,
+            arity: state.arity,
+            chunk: state.chunk,
+            upvalues: state.upvalues,
+            is_initializer: state.kind == FunctionKind::Initializer,
+        });
+        let constant = self.state().chunk.add_constant(Constant::Function(function));
+        self.emit(Op::Closure(constant), name);
+    }
+
+    fn compile_call(&mut self, callee: &Expr, paren: &Token, arguments: &Vec<Expr>, tail: bool) {
+        let mut call_site = (*paren).clone();
+        call_site.span = callee.span().to(&paren.span);
+        self.compile_expr(callee);
+        for arg in arguments {
+            self.compile_expr(arg);
+        }
+        let site = self.state().chunk.add_token(&call_site);
+        let argc = arguments.len();
+        self.emit(if tail { Op::TailCall { argc, site } } else { Op::Call { argc, site } }, paren);
+    }
+
+    // Cleans up every context above `until` on the way out of them: handlers are
+    // uninstalled and finally blocks run inline, innermost first.
+    fn exit_contexts(&mut self, until: usize) {
+        let mut i = self.state().contexts.len();
+        while i > until {
+            i -= 1;
+            match &self.state().contexts[i] {
+                Context::Handler => {
+                    self.emit_op(Op::PopHandler);
+                }
+                Context::Pending => {
+                    self.emit_op(Op::PopPending);
+                }
+                Context::Finally { body, locals } => {
+                    let body = body.clone();
+                    let locals = *locals;
+                    // the finally block sees the contexts and names from outside its try
+                    let inner = self.state().contexts.split_off(i);
+                    let hidden: Vec<String> = self.state().locals[locals..]
+                        .iter_mut()
+                        .map(|local| mem::take(&mut local.name))
+                        .collect();
+                    self.compile_block(&body);
+                    for (local, name) in self.state().locals[locals..].iter_mut().zip(hidden) {
+                        local.name = name;
+                    }
+                    self.state().contexts.extend(inner);
+                }
+                Context::Loop { .. } => {}
+            }
+        }
+    }
+
+    fn innermost_loop(&mut self) -> usize {
+        self.state()
+            .contexts
+            .iter()
+            .rposition(|context| matches!(context, Context::Loop { .. }))
+            .expect("the parser rejects break and continue outside loops")
+    }
+
+    fn jump_out_of_loop(&mut self, keyword: &Token, is_break: bool) {
+        let index = self.innermost_loop();
+        self.exit_contexts(index + 1);
+        let locals = match &self.state().contexts[index] {
+            Context::Loop { locals, .. } => *locals,
+            _ => unreachable!(),
+        };
+        self.discard_locals(locals);
+        let jump = self.emit(Op::Jump(0), keyword);
+        match &mut self.state().contexts[index] {
+            Context::Loop { breaks, .. } if is_break => breaks.push(jump),
+            Context::Loop { continues, .. } => continues.push(jump),
+            _ => unreachable!(),
+        }
+    }
+}
+
+impl expr::Visitor<()> for Compiler {
+    fn visit_unary(&mut self, op: &Token, value: &Box<Expr>) {
+        self.compile_expr(value);
+        self.emit(Op::Unary, op);
+    }
+
+    // operands are evaluated right to left, as in the tree-walker
+    fn visit_binary(&mut self, op: &Token, left: &Box<Expr>, right: &Box<Expr>) {
+        self.compile_expr(right);
+        self.compile_expr(left);
+        self.emit(Op::Binary, op);
+    }
+
+    fn visit_grouping(&mut self, expr: &Box<Expr>) {
+        self.compile_expr(expr);
+    }
+
+    fn visit_literal(&mut self, value: &Token) {
+        let object = ops::literal(value).expect("the parser only produces valid literals");
+        let constant = self.state().chunk.add_constant(Constant::Value(object));
+        self.emit(Op::Constant(constant), value);
+    }
+
+    fn visit_variable(&mut self, name: &Token, id: usize) {
+        self.get_variable(name);
+    }
+
+    fn visit_assign(&mut self, name: &Token, value: &Box<Expr>, id: usize) {
+        self.compile_expr(value);
+        self.set_variable(name);
+    }
+
+    fn visit_call(&mut self, callee: &Box<Expr>, paren: &Token, arguments: &Vec<Expr>) {
+        self.compile_call(callee, paren, arguments, false);
+    }
+
+    fn visit_this(&mut self, keyword: &Token, id: usize) {
+        self.get_variable(keyword);
+    }
+
+    fn visit_super(&mut self, keyword: &Token, method: &Token, id: usize) {
+        let this = Token { lexeme: "this".to_string(), ..(*keyword).clone() };
+        self.get_variable(&this);
+        self.get_variable(keyword);
+        self.emit(Op::GetSuper, method);
+    }
+
+    // a short-circuited `and`/`or` produces a boolean, the other operand's value otherwise
+    fn visit_logical(&mut self, op: &Token, left: &Box<Expr>, right: &Box<Expr>) {
+        self.compile_expr(left);
+        let short_circuit = self.emit(Op::JumpIfFalse(0), op);
+        if op.token_type == TokenType::AND {
+            self.compile_expr(right);
+            let end = self.emit(Op::Jump(0), op);
+            self.patch(short_circuit);
+            self.emit(Op::False, op);
+            self.patch(end);
+        } else {
+            self.emit(Op::True, op);
+            let end = self.emit(Op::Jump(0), op);
+            self.patch(short_circuit);
+            self.compile_expr(right);
+            self.patch(end);
+        }
+    }
+
+    fn visit_set(&mut self, object: &Box<Expr>, name: &Token, value: &Box<Expr>) {
+        self.compile_expr(object);
+        self.emit(Op::CheckFieldTarget, name);
+        self.compile_expr(value);
+        self.emit(Op::SetProperty, name);
+    }
+
+    fn visit_get(&mut self, object: &Box<Expr>, name: &Token) {
+        self.compile_expr(object);
+        self.emit(Op::GetProperty, name);
+    }
+
+    fn visit_list(&mut self, bracket: &Token, elements: &Vec<Expr>) {
+        for element in elements {
+            self.compile_expr(element);
+        }
+        self.emit(Op::List(elements.len()), bracket);
+    }
+
+    fn visit_map(&mut self, brace: &Token, entries: &Vec<(Expr, Expr)>) {
+        for (key, value) in entries {
+            self.compile_expr(key);
+            self.emit(Op::CheckKey, brace);
+            self.compile_expr(value);
+        }
+        self.emit(Op::Map(entries.len()), brace);
+    }
+
+    fn visit_index(&mut self, object: &Box<Expr>, bracket: &Token, index: &Box<Expr>) {
+        self.compile_expr(object);
+        self.compile_expr(index);
+        self.emit(Op::Index, bracket);
+    }
+
+    fn visit_set_index(&mut self, object: &Box<Expr>, bracket: &Token, index: &Box<Expr>, value: &Box<Expr>) {
+        self.compile_expr(object);
+        self.compile_expr(index);
+        self.compile_expr(value);
+        self.emit(Op::SetIndex, bracket);
+    }
+
+    fn visit_interpolation(&mut self, start: &Token, parts: &Vec<Expr>) {
+        for part in parts {
+            self.compile_expr(part);
+        }
+        self.emit(Op::Interpolate(parts.len()), start);
+    }
+
+    fn visit_lambda(&mut self, name: &Token, params: &Vec<Token>, body: &Vec<Stmt>) {
+        self.compile_function(name, params, body, FunctionKind::Function);
+    }
+
+    // A postfix update first pushes a placeholder, which the old value is poked into
+    // once read; the new value left above it is popped at the end.
+    fn visit_update(&mut self, target: &Box<Expr>, op: &Token, value: &Box<Expr>, postfix: bool) {
+        if postfix {
+            self.emit(Op::Null, op);
+        }
+        match &**target {
+            Expr::Variable { name, .. } => {
+                self.compile_expr(value);
+                self.get_variable(name);
+                if postfix {
+                    self.emit(Op::Poke(2), op);
+                }
+                self.emit(Op::Binary, op);
+                self.set_variable(name);
+            }
+            Expr::Get { object, name } => {
+                self.compile_expr(object);
+                self.emit(Op::CheckFieldTarget, name);
+                self.compile_expr(value);
+                self.emit(Op::Peek(1), name);
+                self.emit(Op::GetProperty, name);
+                if postfix {
+                    self.emit(Op::Poke(3), op);
+                }
+                self.emit(Op::Binary, op);
+                self.emit(Op::SetProperty, name);
+            }
+            Expr::Index { object, bracket, index, .. } => {
+                self.compile_expr(object);
+                self.compile_expr(index);
+                self.compile_expr(value);
+                self.emit(Op::Peek(2), bracket);
+                self.emit(Op::Peek(2), bracket);
+                self.emit(Op::Index, bracket);
+                if postfix {
+                    self.emit(Op::Poke(4), op);
+                }
+                self.emit(Op::Binary, op);
+                self.emit(Op::SetIndex, bracket);
+            }
+            _ => unreachable!("the parser only builds updates of variables, properties and indexes"),
+        }
+        if postfix {
+            self.emit(Op::Pop, op);
+        }
+    }
+}
+
+impl stmt::Visitor<()> for Compiler {
+    fn visit_block(&mut self, stmts: &Vec<Stmt>) {
+        self.compile_block(stmts);
+    }
+
+    fn visit_break(&mut self, keyword: &Token) {
+        self.jump_out_of_loop(keyword, true);
+    }
+
+    fn visit_continue(&mut self, keyword: &Token) {
+        self.jump_out_of_loop(keyword, false);
+    }
+
+    fn visit_expression(&mut self, expr: &Expr) {
+        self.compile_expr(expr);
+        self.emit_op(Op::Pop);
+    }
+
+    fn visit_class(&mut self, name: &Token, superclass: &Option<Expr>, methods: &Vec<Stmt>) {
+        let is_global = self.state().scope_depth == 0;
+        if !is_global {
+            self.emit(Op::Null, name);
+            self.add_local(&name.lexeme);
+        }
+
+        // methods of a subclass capture `super` from a scope of its own
+        if let Some(superclass) = superclass {
+            let token = match superclass {
+                Expr::Variable { name, .. } => name,
+                _ => name,
+            };
+            self.begin_scope();
+            self.compile_expr(superclass);
+            self.emit(Op::CheckSuperclass, token);
+            self.add_local("super");
+        }
+
+        for method in methods {
+            if let Stmt::Function { name, params, body, .. } = method {
+                let kind = if name.lexeme == "init" {
+                    FunctionKind::Initializer
+                } else {
+                    FunctionKind::Method
+                };
+                self.compile_function(name, params, body, kind);
+            }
+        }
+        self.emit(
+            Op::Class {
+                methods: methods.len(),
+                has_superclass: superclass.is_some(),
+            },
+            name,
+        );
+
+        if is_global {
+            self.emit(Op::DefineGlobal, name);
+        } else {
+            self.set_variable(name);
+            self.emit_op(Op::Pop);
+        }
+        if superclass.is_some() {
+            self.end_scope();
+        }
+    }
+
+    fn visit_function(&mut self, name: &Token, params: &Vec<Token>, body: &Vec<Stmt>) {
+        // declared first so the body can refer to itself recursively
+        self.declare_variable(name);
+        self.compile_function(name, params, body, FunctionKind::Function);
+        self.define_variable(name);
+    }
+
+    fn visit_import(&mut self, keyword: &Token, path: &Token, name: &Token) {
+        let path = self.state().chunk.add_token(path);
+        self.emit(Op::Import(path), keyword);
+        self.declare_variable(name);
+        self.define_variable(name);
+    }
+
+    fn visit_if(&mut self, condition: &Expr, then_branch: &Stmt, else_branch: &Option<Box<Stmt>>) {
+        self.compile_expr(condition);
+        let skip_then = self.emit_op(Op::JumpIfFalse(0));
+        then_branch.accept(self);
+        match else_branch {
+            Some(else_branch) => {
+                let skip_else = self.emit_op(Op::Jump(0));
+                self.patch(skip_then);
+                else_branch.accept(self);
+                self.patch(skip_else);
+            }
+            None => self.patch(skip_then),
+        }
+    }
+
+    fn visit_print(&mut self, expr: &Expr) {
+        self.compile_expr(expr);
+        self.emit_op(Op::Print);
+    }
+
+    fn visit_return(&mut self, keyword: &Token, value: &Option<Expr>) {
+        // a tail call can replace this frame only when nothing is left to run on the way out
+        let tail = matches!(self.state().kind, FunctionKind::Function | FunctionKind::Method)
+            && self.state().contexts.iter().all(|context| matches!(context, Context::Loop { .. }));
+        match value {
+            Some(Expr::Call { callee, paren, arguments }) if tail => self.compile_call(callee, paren, arguments, true),
+            Some(value) => self.compile_expr(value),
+            None => {
+                self.emit(Op::Null, keyword);
+            }
+        }
+        // the value waits in a slot of its own while any finally blocks run
+        if !self.state().contexts.is_empty() {
+            self.add_local("");
+            self.exit_contexts(0);
+            self.state().locals.pop();
+        }
+        self.emit(Op::Return, keyword);
+    }
+
+    fn visit_throw(&mut self, keyword: &Token, value: &Expr) {
+        self.compile_expr(value);
+        self.emit(Op::Throw, keyword);
+    }
+
+    fn visit_try(
+        &mut self,
+        body: &Vec<Stmt>,
+        catch_name: &Option<Token>,
+        catch_body: &Option<Vec<Stmt>>,
+        finally_body: &Option<Vec<Stmt>>,
+    ) {
+        let locals = self.state().locals.len();
+        let mut finally_handler = 0;
+        if let Some(finally_body) = finally_body {
+            self.state().contexts.push(Context::Finally {
+                body: finally_body.clone(),
+                locals,
+            });
+            finally_handler = self.emit_op(Op::PushFinally(0));
+            self.state().contexts.push(Context::Handler);
+        }
+
+        match catch_body {
+            Some(catch_body) => {
+                let catch_handler = self.emit_op(Op::PushCatch(0));
+                self.state().contexts.push(Context::Handler);
+                self.compile_block(body);
+                self.state().contexts.pop();
+                self.emit_op(Op::PopHandler);
+                let skip_catch = self.emit_op(Op::Jump(0));
+
+                // the VM pushes the error object into the catch variable's slot
+                self.patch(catch_handler);
+                self.begin_scope();
+                match catch_name {
+                    Some(name) => self.add_local(&name.lexeme),
+                    None => self.add_local(""),
+                }
+                for stmt in catch_body {
+                    stmt.accept(self);
+                }
+                self.end_scope();
+                self.patch(skip_catch);
+            }
+            None => self.compile_block(body),
+        }
+
+        if let Some(finally_body) = finally_body {
+            self.state().contexts.pop();
+            self.emit_op(Op::PopHandler);
+            self.state().contexts.pop();
+            self.compile_block(finally_body);
+            let skip_rethrow = self.emit_op(Op::Jump(0));
+
+            self.patch(finally_handler);
+            self.state().contexts.push(Context::Pending);
+            self.compile_block(finally_body);
+            self.state().contexts.pop();
+            self.emit_op(Op::Rethrow);
+            self.patch(skip_rethrow);
+        }
+    }
+
+    fn visit_var(&mut self, name: &Token, initializer: &Option<Expr>) {
+        match initializer {
+            Some(initializer) => self.compile_expr(initializer),
+            None => {
+                self.emit(Op::Null, name);
+            }
+        }
+        self.declare_variable(name);
+        self.define_variable(name);
+    }
+
+    fn visit_while(&mut self, condition: &Expr, body: &Stmt, increment: &Option<Expr>, per_iteration: bool) {
+        // the loop variable's slot: the first local of the scope around the loop
+        let depth = self.state().scope_depth;
+        let first = self.state().locals.iter().position(|local| local.depth == depth);
+        let start = self.here();
+        self.compile_expr(condition);
+        let exit = self.emit_op(Op::JumpIfFalse(0));
+
+        let locals = self.state().locals.len();
+        self.state().contexts.push(Context::Loop {
+            locals,
+            breaks: Vec::new(),
+            continues: Vec::new(),
+        });
+        body.accept(self);
+        let (breaks, continues) = match self.state().contexts.pop() {
+            Some(Context::Loop { breaks, continues, .. }) => (breaks, continues),
+            _ => unreachable!(),
+        };
+
+        for jump in continues {
+            self.patch(jump);
+        }
+        if let (true, Some(slot)) = (per_iteration, first) {
+            self.emit_op(Op::CloseUpvalues(slot));
+        }
+        if let Some(increment) = increment {
+            self.compile_expr(increment);
+            self.emit_op(Op::Pop);
+        }
+        self.emit_op(Op::Jump(start));
+        self.patch(exit);
+        for jump in breaks {
+            self.patch(jump);
+        }
+    }
+}
@@ -0,0 +1,5 @@
+mod chunk;
+mod compiler;
+mod vm;
+
+pub use vm::{Closure, Vm};
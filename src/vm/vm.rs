@@ -0,0 +1,603 @@
+use super::chunk::{Constant, FunctionProto, Op};
+use super::compiler::Compiler;
+use crate::ast::stmt::Stmt;
+use crate::class::{Class, Instance, Method};
+use crate::diagnostic::SourceMap;
+use crate::error::RuntimeError;
+use crate::interpreter::{Frame, DEFAULT_MAX_CALL_DEPTH};
+use crate::list::new_list;
+use crate::map::{self, Map};
+use crate::module::{self, Imports, Module};
+use crate::native::{self, Runtime};
+use crate::object::Object;
+use crate::optimizer;
+use crate::ops;
+use crate::resolver::Resolver;
+use crate::scope::Scope;
+use crate::token::Token;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt;
+use std::path::PathBuf;
+use std::rc::Rc;
+
+// A variable captured by a closure: a stack slot while the declaring function
+// is still running, then the value itself once that slot is popped.
+#[derive(Debug)]
+pub enum Upvalue {
+    Open(usize),
+    Closed(Object),
+}
+
+#[derive(Debug)]
+pub struct Closure {
+    pub function: Rc<FunctionProto>,
+    pub upvalues: Vec<Rc<RefCell<Upvalue>>>,
+    // top-level scope of the file the function was declared in
+    pub globals: Rc<RefCell<Scope>>,
+}
+
+impl fmt::Display for Closure {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.function)
+    }
+}
+
+struct CallFrame {
+    closure: Rc<Closure>,
+    ip: usize,
+    // stack index of slot 0
+    base: usize,
+    // whether the call is on `call_stack`; scripts and modules are not
+    traced: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum HandlerKind {
+    Catch,
+    Finally,
+}
+
+// An installed catch or finally, with the state to unwind back to.
+struct Handler {
+    kind: HandlerKind,
+    target: usize,
+    frames: usize,
+    stack_len: usize,
+    pending_len: usize,
+}
+
+// Stack-based virtual machine running compiled chunks. It behaves like the
+// tree-walking `Interpreter`: same values, natives, modules and errors.
+pub struct Vm {
+    builtins: Rc<RefCell<Scope>>,
+    globals: Rc<RefCell<Scope>>,
+    stack: Vec<Object>,
+    frames: Vec<CallFrame>,
+    open_upvalues: Vec<Rc<RefCell<Upvalue>>>,
+    handlers: Vec<Handler>,
+    // errors waiting for their finally block to finish before propagating
+    pending: Vec<RuntimeError>,
+    error_class: Rc<Class>,
+    pub imports: Imports,
+    modules: HashMap<PathBuf, Object>,
+    pub sources: SourceMap,
+    call_stack: Vec<Frame>,
+    max_call_depth: usize,
+}
+
+impl Vm {
+    pub fn new() -> Vm {
+        let builtins = Rc::new(RefCell::new(Scope::new()));
+        for func in native::prelude() {
+            builtins.borrow_mut().define(func.name.clone(), Object::Native { func });
+        }
+        Vm {
+            globals: Rc::new(RefCell::new(Scope::from(&builtins))),
+            builtins,
+            stack: Vec::new(),
+            frames: Vec::new(),
+            open_upvalues: Vec::new(),
+            handlers: Vec::new(),
+            pending: Vec::new(),
+            error_class: Rc::new(Class::new("Error".to_string(), None, HashMap::new())),
+            imports: Imports::new(),
+            modules: HashMap::new(),
+            sources: SourceMap::new(),
+            call_stack: Vec::new(),
+            max_call_depth: DEFAULT_MAX_CALL_DEPTH,
+        }
+    }
+
+    pub fn set_max_call_depth(&mut self, depth: usize) {
+        self.max_call_depth = depth;
+    }
+
+    // compiles and runs resolved statements
+    pub fn interpret(&mut self, stmts: &Vec<Stmt>) -> Result<(), RuntimeError> {
+        let closure = Closure {
+            function: Compiler::compile(stmts),
+            upvalues: Vec::new(),
+            globals: Rc::clone(&self.globals),
+        };
+        self.execute(Rc::new(closure))
+    }
+
+    // runs a script or module body to completion
+    fn execute(&mut self, closure: Rc<Closure>) -> Result<(), RuntimeError> {
+        let depth = self.frames.len();
+        self.stack.push(Object::Closure {
+            closure: Rc::clone(&closure),
+        });
+        self.frames.push(CallFrame {
+            closure,
+            ip: 0,
+            base: self.stack.len() - 1,
+            traced: false,
+        });
+
+        while self.frames.len() > depth {
+            if let Err(error) = self.step() {
+                self.unwind(error, depth)?;
+            }
+        }
+        self.stack.pop();
+        Ok(())
+    }
+
+    // Sends an error to the innermost handler installed since `depth`, or, when
+    // there is none, pops every frame above `depth` and hands the error back.
+    fn unwind(&mut self, mut error: RuntimeError, depth: usize) -> Result<(), RuntimeError> {
+        let handler = match self.handlers.last() {
+            Some(handler) if handler.frames > depth => self.handlers.pop().unwrap(),
+            _ => {
+                while self.frames.len() > depth {
+                    error = self.pop_frame(error);
+                }
+                return Err(error);
+            }
+        };
+
+        while self.frames.len() > handler.frames {
+            error = self.pop_frame(error);
+        }
+        self.close_upvalues(handler.stack_len);
+        self.stack.truncate(handler.stack_len);
+        self.pending.truncate(handler.pending_len);
+        self.frames.last_mut().unwrap().ip = handler.target;
+        match handler.kind {
+            HandlerKind::Catch => self.stack.push(error.to_object(&self.error_class)),
+            HandlerKind::Finally => self.pending.push(error),
+        }
+        Ok(())
+    }
+
+    fn pop_frame(&mut self, error: RuntimeError) -> RuntimeError {
+        let frame = self.frames.pop().unwrap();
+        self.close_upvalues(frame.base);
+        self.stack.truncate(frame.base);
+        if frame.traced {
+            let call = self.call_stack.pop().unwrap();
+            return error.with_frame(call);
+        }
+        error
+    }
+
+    fn pop(&mut self) -> Object {
+        self.stack.pop().expect("the compiler keeps the stack balanced")
+    }
+
+    fn peek(&self) -> &Object {
+        self.stack.last().expect("the compiler keeps the stack balanced")
+    }
+
+    fn step(&mut self) -> Result<(), RuntimeError> {
+        let frame = self.frames.last_mut().unwrap();
+        let closure = Rc::clone(&frame.closure);
+        let base = frame.base;
+        let ip = frame.ip;
+        frame.ip += 1;
+
+        let chunk = &closure.function.chunk;
+        let token = chunk.token_at(ip);
+        match chunk.code[ip] {
+            Op::Constant(index) => match &chunk.constants[index] {
+                Constant::Value(value) => self.stack.push(value.clone()),
+                Constant::Function(_) => unreachable!("functions are loaded with Op::Closure"),
+            },
+            Op::Null => self.stack.push(Object::Null),
+            Op::True => self.stack.push(Object::Boolean { value: true }),
+            Op::False => self.stack.push(Object::Boolean { value: false }),
+            Op::Pop => {
+                self.pop();
+            }
+            Op::Peek(n) => self.stack.push(self.stack[self.stack.len() - 1 - n].clone()),
+            Op::Poke(n) => {
+                let top = self.stack.len() - 1;
+                self.stack[top - n] = self.peek().clone();
+            }
+
+            Op::GetLocal(slot) => self.stack.push(self.stack[base + slot].clone()),
+            Op::SetLocal(slot) => self.stack[base + slot] = self.peek().clone(),
+            Op::GetUpvalue(index) => {
+                let value = match &*closure.upvalues[index].borrow() {
+                    Upvalue::Open(slot) => self.stack[*slot].clone(),
+                    Upvalue::Closed(value) => value.clone(),
+                };
+                self.stack.push(value);
+            }
+            Op::SetUpvalue(index) => {
+                let value = self.peek().clone();
+                match &mut *closure.upvalues[index].borrow_mut() {
+                    Upvalue::Open(slot) => self.stack[*slot] = value,
+                    Upvalue::Closed(closed) => *closed = value,
+                }
+            }
+            Op::DefineGlobal => {
+                let value = self.pop();
+                closure.globals.borrow_mut().define(token.lexeme.clone(), value);
+            }
+            Op::GetGlobal => {
+                let value = closure.globals.borrow().get(token)?;
+                self.stack.push(value);
+            }
+            Op::SetGlobal => {
+                let value = self.peek().clone();
+                closure.globals.borrow_mut().assign(token, value)?;
+            }
+
+            Op::GetProperty => {
+                let object = self.pop();
+                self.stack.push(ops::get_property(object, token)?);
+            }
+            Op::CheckFieldTarget => ops::check_field_target(self.peek(), token)?,
+            Op::SetProperty => {
+                let value = self.pop();
+                if let Object::Instance { instance } = self.pop() {
+                    instance.borrow_mut().set(token, value.clone());
+                }
+                self.stack.push(value);
+            }
+            Op::GetSuper => {
+                let superclass = self.pop();
+                let this = self.pop();
+                let method = match superclass {
+                    Object::Class { class } => class.find_method(&token.lexeme),
+                    _ => None,
+                };
+                match method {
+                    Some(method) => self.stack.push(method.bind(this)),
+                    None => return Err(RuntimeError::UndefinedProperty((*token).clone())),
+                }
+            }
+            Op::Index => {
+                let index = self.pop();
+                let object = self.pop();
+                self.stack.push(ops::index(object, &index, token)?);
+            }
+            Op::SetIndex => {
+                let value = self.pop();
+                let index = self.pop();
+                let object = self.pop();
+                self.stack.push(ops::set_index(object, index, value, token)?);
+            }
+            Op::List(count) => {
+                let elements = self.stack.split_off(self.stack.len() - count);
+                self.stack.push(new_list(elements));
+            }
+            Op::Interpolate(count) => {
+                let parts = self.stack.split_off(self.stack.len() - count);
+                self.stack.push(ops::interpolate(parts));
+            }
+            Op::CheckKey => map::check_key(self.peek(), token)?,
+            Op::Map(count) => {
+                let values = self.stack.split_off(self.stack.len() - 2 * count);
+                let mut entries = Map::new();
+                let mut values = values.into_iter();
+                while let (Some(key), Some(value)) = (values.next(), values.next()) {
+                    entries.insert(key, value);
+                }
+                self.stack.push(map::new_map(entries));
+            }
+
+            Op::Unary => {
+                let value = self.pop();
+                self.stack.push(ops::unary(token, value)?);
+            }
+            Op::Binary => {
+                let left = self.pop();
+                let right = self.pop();
+                self.stack.push(ops::binary(token, left, right)?);
+            }
+
+            Op::Print => println!("{}", self.pop()),
+            Op::Jump(target) => self.frames.last_mut().unwrap().ip = target,
+            Op::JumpIfFalse(target) => {
+                if !self.pop().is_truthy() {
+                    self.frames.last_mut().unwrap().ip = target;
+                }
+            }
+
+            Op::Call { argc, site } => self.call(argc, token, &chunk.tokens[site])?,
+            Op::TailCall { argc, site } => self.tail_call(argc, token, &chunk.tokens[site])?,
+            Op::Closure(index) => {
+                let function = match &chunk.constants[index] {
+                    Constant::Function(function) => Rc::clone(function),
+                    Constant::Value(_) => unreachable!("Op::Closure always refers to a function"),
+                };
+                let upvalues = function
+                    .upvalues
+                    .iter()
+                    .map(|&(is_local, index)| {
+                        if is_local {
+                            self.capture_upvalue(base + index)
+                        } else {
+                            Rc::clone(&closure.upvalues[index])
+                        }
+                    })
+                    .collect();
+                self.stack.push(Object::Closure {
+                    closure: Rc::new(Closure {
+                        function,
+                        upvalues,
+                        globals: Rc::clone(&closure.globals),
+                    }),
+                });
+            }
+            Op::CloseUpvalue => {
+                self.close_upvalues(self.stack.len() - 1);
+                self.pop();
+            }
+            Op::CloseUpvalues(slot) => self.close_upvalues(base + slot),
+            Op::Return => {
+                let mut value = self.pop();
+                let frame = self.frames.pop().unwrap();
+                // an initializer always hands back the instance, even on a bare `return;`
+                if frame.closure.function.is_initializer {
+                    value = self.stack[frame.base].clone();
+                }
+                self.close_upvalues(frame.base);
+                self.stack.truncate(frame.base);
+                if frame.traced {
+                    self.call_stack.pop();
+                }
+                self.stack.push(value);
+            }
+
+            Op::CheckSuperclass => {
+                if !matches!(self.peek(), Object::Class { .. }) {
+                    return Err(RuntimeError::InvalidSuperclass(
+                        (*token).clone(),
+                        format!("Superclass must be a class, found {}", self.peek()),
+                    ));
+                }
+            }
+            Op::Class { methods, has_superclass } => {
+                let closures = self.stack.split_off(self.stack.len() - methods);
+                let superclass = match self.stack.last() {
+                    Some(Object::Class { class }) if has_superclass => Some(Rc::clone(class)),
+                    _ => None,
+                };
+                let mut class_methods: HashMap<String, Method> = HashMap::new();
+                for method in closures {
+                    if let Object::Closure { closure } = method {
+                        class_methods.insert(closure.function.name.lexeme.clone(), Method::Closure(closure));
+                    }
+                }
+                self.stack.push(Object::Class {
+                    class: Rc::new(Class::new(token.lexeme.clone(), superclass, class_methods)),
+                });
+            }
+            Op::Import(path) => {
+                let module = self.import_module(token, &chunk.tokens[path])?;
+                self.stack.push(module);
+            }
+
+            Op::Throw => {
+                let value = self.pop();
+                return Err(RuntimeError::thrown(token, value));
+            }
+            Op::PushCatch(target) => self.push_handler(HandlerKind::Catch, target),
+            Op::PushFinally(target) => self.push_handler(HandlerKind::Finally, target),
+            Op::PopHandler => {
+                self.handlers.pop();
+            }
+            Op::PopPending => {
+                self.pending.pop();
+            }
+            Op::Rethrow => return Err(self.pending.pop().expect("a finally block only rethrows its own error")),
+        }
+        Ok(())
+    }
+
+    fn push_handler(&mut self, kind: HandlerKind, target: usize) {
+        self.handlers.push(Handler {
+            kind,
+            target,
+            frames: self.frames.len(),
+            stack_len: self.stack.len(),
+            pending_len: self.pending.len(),
+        });
+    }
+
+    // the callee sits below its `argc` arguments on the stack
+    fn call(&mut self, argc: usize, paren: &Token, call_site: &Token) -> Result<(), RuntimeError> {
+        let base = self.stack.len() - argc - 1;
+        let callee = self.stack[base].clone();
+
+        let (name, min_arity, arity, declaration) = match &callee {
+            Object::Closure { closure: function } | Object::BoundMethod { method: function, .. } => (
+                function.function.name.lexeme.clone(),
+                function.function.arity,
+                function.function.arity,
+                Some(function.function.name.clone()),
+            ),
+            Object::Native { func } => (func.name.clone(), func.min_arity(), func.arity(), None),
+            Object::Class { class } => (
+                class.name.clone(),
+                class.arity(),
+                class.arity(),
+                class.find_method("init").map(|init| init.name().clone()),
+            ),
+            _ => {
+                return Err(RuntimeError::InvalidFunctionCall(
+                    (*paren).clone(),
+                    "Can only call functions and classes".to_string(),
+                ))
+            }
+        };
+        if argc < min_arity || argc > arity {
+            return Err(RuntimeError::ArityMismatch((*call_site).clone(), arity, argc, declaration));
+        }
+
+        if self.call_stack.len() >= self.max_call_depth {
+            return Err(RuntimeError::StackOverflow((*call_site).clone()));
+        }
+
+        let frame = Frame {
+            function: name,
+            call_site: (*call_site).clone(),
+            elided: 0,
+        };
+        match callee {
+            Object::Closure { closure } => self.push_frame(closure, base, frame),
+            Object::BoundMethod { receiver, method } => {
+                self.stack[base] = *receiver;
+                self.push_frame(method, base, frame);
+            }
+            Object::Native { func } => {
+                let args = self.stack.split_off(base + 1);
+                self.stack.pop();
+                self.call_stack.push(frame.clone());
+                let result = func.call(self, paren, args);
+                self.call_stack.pop();
+                self.stack.push(result.map_err(|error| error.with_frame(frame))?);
+            }
+            Object::Class { class } => {
+                self.stack[base] = Object::Instance {
+                    instance: Rc::new(RefCell::new(Instance::new(Rc::clone(&class)))),
+                };
+                // without an initializer there are no arguments, and the instance is the result
+                if let Some(Method::Closure(init)) = class.find_method("init") {
+                    self.push_frame(init, base, frame);
+                }
+            }
+            _ => unreachable!(),
+        }
+        Ok(())
+    }
+
+    // Runs a function being returned from the current one in the current one's
+    // frame. Anything but a closure with the right arity is called as usual, and
+    // the `Return` that follows hands back its result.
+    fn tail_call(&mut self, argc: usize, paren: &Token, call_site: &Token) -> Result<(), RuntimeError> {
+        let callee_slot = self.stack.len() - argc - 1;
+        let (closure, receiver) = match &self.stack[callee_slot] {
+            Object::Closure { closure } if closure.function.arity == argc => (Rc::clone(closure), None),
+            Object::BoundMethod { receiver, method } if method.function.arity == argc => {
+                (Rc::clone(method), Some((**receiver).clone()))
+            }
+            _ => return self.call(argc, paren, call_site),
+        };
+
+        let base = self.frames.last().unwrap().base;
+        self.close_upvalues(base);
+        let mut args = self.stack.split_off(callee_slot);
+        if let Some(receiver) = receiver {
+            args[0] = receiver;
+        }
+        self.stack.truncate(base);
+        self.stack.extend(args);
+
+        let frame = self.frames.last_mut().unwrap();
+        frame.closure = Rc::clone(&closure);
+        frame.ip = 0;
+        if frame.traced {
+            let call = self.call_stack.last_mut().unwrap();
+            call.function = closure.function.name.lexeme.clone();
+            call.elided += 1;
+        }
+        Ok(())
+    }
+
+    fn push_frame(&mut self, closure: Rc<Closure>, base: usize, frame: Frame) {
+        self.call_stack.push(frame);
+        self.frames.push(CallFrame {
+            closure,
+            ip: 0,
+            base,
+            traced: true,
+        });
+    }
+
+    fn capture_upvalue(&mut self, slot: usize) -> Rc<RefCell<Upvalue>> {
+        let existing = self
+            .open_upvalues
+            .iter()
+            .find(|upvalue| matches!(*upvalue.borrow(), Upvalue::Open(open) if open == slot));
+        if let Some(upvalue) = existing {
+            return Rc::clone(upvalue);
+        }
+        let upvalue = Rc::new(RefCell::new(Upvalue::Open(slot)));
+        self.open_upvalues.push(Rc::clone(&upvalue));
+        upvalue
+    }
+
+    // moves every captured slot at or above `from` off the stack and into its upvalue
+    fn close_upvalues(&mut self, from: usize) {
+        let stack = &self.stack;
+        self.open_upvalues.retain(|upvalue| {
+            let mut upvalue = upvalue.borrow_mut();
+            match *upvalue {
+                Upvalue::Open(slot) if slot >= from => {
+                    *upvalue = Upvalue::Closed(stack[slot].clone());
+                    false
+                }
+                _ => true,
+            }
+        });
+    }
+
+    fn import_module(&mut self, keyword: &Token, path: &Token) -> Result<Object, RuntimeError> {
+        let import_error = |msg: String| RuntimeError::ImportError((*keyword).clone(), msg);
+
+        let canonical = module::locate(&self.imports.current_dir, path.value()).map_err(import_error)?;
+        if let Some(module) = self.modules.get(&canonical) {
+            return Ok(module.clone());
+        }
+        module::check_cycle(&self.imports.importing, &canonical).map_err(import_error)?;
+
+        let stmts = module::parse(&canonical, path.value(), &mut self.sources).map_err(import_error)?;
+        if let Err(errors) = Resolver::standalone().resolve(&stmts) {
+            return Err(import_error(module::resolve_failure(path.value(), &errors)));
+        }
+        let stmts = optimizer::optimize(stmts);
+
+        // run the module in a fresh top-level scope
+        let module_scope = Rc::new(RefCell::new(Scope::from(&self.builtins)));
+        let closure = Closure {
+            function: Compiler::compile(&stmts),
+            upvalues: Vec::new(),
+            globals: Rc::clone(&module_scope),
+        };
+        let previous_dir = self.imports.enter(&canonical);
+
+        let result = self.execute(Rc::new(closure));
+
+        self.imports.leave(previous_dir);
+        result?;
+
+        let name = module::default_name(path.value()).unwrap_or_else(|| path.value().to_string());
+        let module = Object::Module {
+            module: Rc::new(Module::new(name, canonical.clone(), module_scope)),
+        };
+        self.modules.insert(canonical, module.clone());
+        Ok(module)
+    }
+}
+
+impl Runtime for Vm {
+    fn call_stack(&self) -> &[Frame] {
+        &self.call_stack
+    }
+}
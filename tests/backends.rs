@@ -0,0 +1,115 @@
+mod common;
+
+use common::run;
+
+// Runs `source` through `interp` and `run`, checking both succeed with the same output.
+fn assert_backends_agree(source: &str, expected: &str) {
+    let interp = run("interp", source);
+    let vm = run("run", source);
+    assert_eq!(interp.code, Some(0), "interp failed:\n{}", interp.stderr);
+    assert_eq!(vm.code, Some(0), "run failed:\n{}", vm.stderr);
+    assert_eq!(interp.stdout, vm.stdout, "the backends disagree");
+    assert_eq!(interp.stdout, expected);
+}
+
+#[test]
+fn closures_share_and_outlive_their_upvalues() {
+    let source = "
+        fn counter(start) {
+            var count = start;
+            fn increment() { count = count + 1; return count; }
+            fn peek() { return count; }
+            return [increment, peek];
+        }
+        var a = counter(0);
+        var b = counter(10);
+        a[0]();
+        a[0]();
+        b[0]();
+        print(a[1]());
+        print(b[1]());
+
+        fn outer() {
+            var x = \"outer\";
+            fn middle() {
+                fn inner() { return x; }
+                return inner;
+            }
+            return middle();
+        }
+        print(outer()());
+    ";
+    assert_backends_agree(source, "2\n11\nouter\n");
+}
+
+#[test]
+fn classes_inherit_and_call_super() {
+    let source = "
+        class Shape {
+            init(name) { this.name = name; }
+            describe() { return this.name + \" with area \" + str(this.area()); }
+            area() { return 0; }
+        }
+        class Square < Shape {
+            init(side) { super.init(\"square\"); this.side = side; }
+            area() { return this.side * this.side; }
+            describe() { return \"a \" + super.describe(); }
+        }
+        var s = Square(3);
+        print(s.describe());
+        print(s.name);
+        var describe = s.describe;
+        print(describe());
+    ";
+    assert_backends_agree(
+        source,
+        "a square with area 9\nsquare\na square with area 9\n",
+    );
+}
+
+#[test]
+fn try_catch_and_finally_run_in_order() {
+    let source = "
+        fn risky(fail) {
+            try {
+                if (fail) { throw \"bad\"; }
+                return \"ok\";
+            } finally {
+                print(\"cleanup\");
+            }
+        }
+        print(risky(false));
+        try {
+            risky(true);
+        } catch (e) {
+            print(\"caught \" + e.value);
+        } finally {
+            print(\"done\");
+        }
+        try { [1][5]; } catch (e) { print(e.kind); }
+    ";
+    assert_backends_agree(
+        source,
+        "cleanup\nok\ncleanup\ncaught bad\ndone\nIndexOutOfRange\n",
+    );
+}
+
+#[test]
+fn break_and_continue_in_nested_loops() {
+    let source = "
+        var out = \"\";
+        for (var i = 0; i < 4; i = i + 1) {
+            if (i == 1) { continue; }
+            var j = 0;
+            while (true) {
+                j = j + 1;
+                if (j > i) { break; }
+                if (j == 2) { continue; }
+                out = out + str(i) + str(j) + \" \";
+            }
+            if (i == 3) { break; }
+        }
+        print(out);
+    ";
+    assert_backends_agree(source, "21 31 33 \n");
+}
@@ -0,0 +1,37 @@
+mod common;
+
+use common::{run, run_with_env};
+
+const DIVE: &str = "
+    var depth = 0;
+    fn dive() { depth = depth + 1; dive(); }
+    try { dive(); } catch (e) { print(e.message); }
+    print(depth);
+";
+
+#[test]
+fn salmon_max_depth_sets_where_stack_overflow_is_raised() {
+    for command in ["interp", "run"] {
+        let run = run_with_env(command, DIVE, &[("SALMON_MAX_DEPTH", "50")]);
+        assert_eq!(run.code, Some(0), "{}:\n{}", command, run.stderr);
+        assert_eq!(run.stdout, "Stack Overflow at line 3. Maximum call depth exceeded\n50\n", "{}", command);
+    }
+}
+
+#[test]
+fn the_default_depth_applies_without_salmon_max_depth() {
+    for command in ["interp", "run"] {
+        let run = run(command, DIVE);
+        assert_eq!(run.code, Some(0), "{}:\n{}", command, run.stderr);
+        assert!(run.stdout.ends_with("\n1000\n"), "{}: {}", command, run.stdout);
+    }
+}
+
+#[test]
+fn salmon_max_depth_must_be_a_positive_integer() {
+    for value in ["0", "deep"] {
+        let run = run_with_env("interp", DIVE, &[("SALMON_MAX_DEPTH", value)]);
+        assert_eq!(run.code, Some(64), "{}", value);
+        assert!(run.stderr.contains("SALMON_MAX_DEPTH must be a positive integer"), "{}", run.stderr);
+    }
+}
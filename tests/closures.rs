@@ -0,0 +1,135 @@
+mod common;
+
+use common::assert_prints;
+
+#[test]
+fn parameters_bind_by_name_and_functions_return_null() {
+    let source = "
+        fn describe(name, count) { print(name + \": \" + str(count)); }
+        print(describe(\"apples\", 3));
+    ";
+    assert_prints(source, "apples: 3\nnull\n");
+}
+
+#[test]
+fn closures_created_in_a_loop_keep_their_own_variables() {
+    let source = "
+        var callbacks = [];
+        for (var i = 0; i < 3; i = i + 1) {
+            var captured = i;
+            fn callback() { return captured * 10; }
+            callbacks.push(callback);
+        }
+        print(callbacks[0]());
+        print(callbacks[1]());
+        print(callbacks[2]());
+    ";
+    assert_prints(source, "0\n10\n20\n");
+}
+
+#[test]
+fn each_for_iteration_binds_its_own_loop_variable() {
+    let source = "
+        var fs = [];
+        for (var i = 0; i < 3; i = i + 1) {
+            fs.push(fn() { return i; });
+            if (i == 1) { i = i + 1; continue; }
+        }
+        print(fs[0]());
+        print(fs[1]());
+        print(len(fs));
+    ";
+    assert_prints(source, "0\n2\n2\n");
+}
+
+#[test]
+fn while_loops_share_one_variable_across_iterations() {
+    let source = "
+        var fs = [];
+        var i = 0;
+        while (i < 3) {
+            fs.push(fn() { return i; });
+            i = i + 1;
+        }
+        print(fs[0]());
+        print(fs[2]());
+    ";
+    assert_prints(source, "3\n3\n");
+}
+
+#[test]
+fn counters_created_in_a_loop_are_independent() {
+    let source = "
+        fn counter() {
+            var count = 0;
+            fn increment() { count = count + 1; return count; }
+            return increment;
+        }
+        var counters = [];
+        for (var i = 0; i < 2; i = i + 1) { counters.push(counter()); }
+        counters[0]();
+        counters[0]();
+        print(counters[0]());
+        print(counters[1]());
+    ";
+    assert_prints(source, "3\n1\n");
+}
+
+#[test]
+fn closures_share_the_variable_not_a_copy() {
+    let source = "
+        fn pair() {
+            var value = \"before\";
+            fn get() { return value; }
+            fn set(v) { value = v; }
+            return [get, set];
+        }
+        var p = pair();
+        p[1](\"after\");
+        print(p[0]());
+    ";
+    assert_prints(source, "after\n");
+}
+
+#[test]
+fn nested_functions_see_every_enclosing_scope() {
+    let source = "
+        fn outer(a) {
+            fn middle(b) {
+                fn inner(c) { return a + b + c; }
+                return inner;
+            }
+            return middle;
+        }
+        print(outer(1)(20)(300));
+    ";
+    assert_prints(source, "321\n");
+}
+
+#[test]
+fn closures_resolve_lexically_not_dynamically() {
+    let source = "
+        var name = \"global\";
+        {
+            fn show() { print(name); }
+            show();
+            var name = \"block\";
+            show();
+        }
+    ";
+    assert_prints(source, "global\nglobal\n");
+}
+
+#[test]
+fn recursion() {
+    let source = "
+        fn fib(n) { if (n < 2) return n; return fib(n - 1) + fib(n - 2); }
+        print(fib(20));
+        fn make() {
+            fn fact(n) { if (n <= 1) return 1; return n * fact(n - 1); }
+            return fact;
+        }
+        print(make()(10));
+    ";
+    assert_prints(source, "6765\n3628800\n");
+}
@@ -0,0 +1,71 @@
+// every test crate compiles its own copy of this module and uses only part of it
+#![allow(dead_code)]
+
+use std::env;
+use std::fs;
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Command, Output, Stdio};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+static SCRIPTS: AtomicUsize = AtomicUsize::new(0);
+
+pub struct Run {
+    pub stdout: String,
+    pub stderr: String,
+    pub code: Option<i32>,
+}
+
+impl From<Output> for Run {
+    fn from(output: Output) -> Run {
+        Run {
+            stdout: String::from_utf8_lossy(&output.stdout).into_owned(),
+            stderr: String::from_utf8_lossy(&output.stderr).into_owned(),
+            code: output.status.code(),
+        }
+    }
+}
+
+// Writes `source` to a fresh file and runs it with `command` (`interp` or `run`).
+pub fn run(command: &str, source: &str) -> Run {
+    run_with_env(command, source, &[])
+}
+
+// `run`, with extra environment variables set for the process
+pub fn run_with_env(command: &str, source: &str, vars: &[(&str, &str)]) -> Run {
+    run_script(command, source, vars, "")
+}
+
+// `run`, with `input` piped to the process's standard input
+pub fn run_with_input(command: &str, source: &str, input: &str) -> Run {
+    run_script(command, source, &[], input)
+}
+
+fn run_script(command: &str, source: &str, vars: &[(&str, &str)], input: &str) -> Run {
+    let id = SCRIPTS.fetch_add(1, Ordering::SeqCst);
+    let path: PathBuf = env::temp_dir().join(format!("salmon-test-{}-{}.salmon", std::process::id(), id));
+    fs::write(&path, source).unwrap();
+    let mut child = Command::new(env!("CARGO_BIN_EXE_salmon-rs"))
+        .arg(command)
+        .arg(&path)
+        .env("NO_COLOR", "1")
+        .envs(vars.iter().copied())
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(input.as_bytes()).unwrap();
+    let output = child.wait_with_output().unwrap();
+    fs::remove_file(&path).unwrap();
+    Run::from(output)
+}
+
+// Runs `source` on both backends, checking each succeeds and prints `expected`.
+pub fn assert_prints(source: &str, expected: &str) {
+    for command in ["interp", "run"] {
+        let run = run(command, source);
+        assert_eq!(run.code, Some(0), "{} failed:\n{}", command, run.stderr);
+        assert_eq!(run.stdout, expected, "{} printed the wrong output", command);
+    }
+}
@@ -0,0 +1,132 @@
+mod common;
+
+use common::assert_prints;
+
+#[test]
+fn return_from_nested_blocks_restores_caller_scope() {
+    let source = "
+        var x = \"global\";
+        fn deep() {
+            var x = \"deep\";
+            { { { return x; } } }
+        }
+        {
+            var x = \"caller\";
+            print(deep());
+            print(x);
+        }
+        print(x);
+    ";
+    assert_prints(source, "deep\ncaller\nglobal\n");
+}
+
+#[test]
+fn return_from_inside_a_loop_restores_caller_scope() {
+    let source = "
+        fn find(items, target) {
+            for (var i = 0; i < len(items); i = i + 1) {
+                var item = items[i];
+                if (item == target) return i;
+            }
+            return -1;
+        }
+        var i = \"untouched\";
+        var item = \"also untouched\";
+        print(find([4, 5, 6], 5));
+        print(find([4, 5, 6], 7));
+        print(i);
+        print(item);
+    ";
+    assert_prints(source, "1\n-1\nuntouched\nalso untouched\n");
+}
+
+#[test]
+fn errors_thrown_deep_inside_calls_restore_scope() {
+    let source = "
+        var name = \"outer\";
+        fn fail() {
+            var name = \"inner\";
+            { throw \"boom\"; }
+        }
+        {
+            var name = \"block\";
+            try { fail(); } catch (e) { print(name); }
+            print(name);
+        }
+        print(name);
+    ";
+    assert_prints(source, "block\nblock\nouter\n");
+}
+
+#[test]
+fn return_passes_through_finally_and_try_does_not_catch_it() {
+    let source = "
+        fn pick() {
+            var x = \"kept\";
+            try { return x; } catch (e) { print(\"not an error\"); } finally { print(\"finally\"); }
+            return \"unreachable\";
+        }
+        var x = \"caller\";
+        print(pick());
+        print(x);
+    ";
+    assert_prints(source, "finally\nkept\ncaller\n");
+}
+
+#[test]
+fn break_and_continue_only_leave_their_loop() {
+    let source = "
+        var total = 0;
+        for (var i = 0; i < 5; i = i + 1) {
+            var j = 0;
+            while (true) {
+                j = j + 1;
+                if (j < 3) continue;
+                break;
+            }
+            if (i == 3) continue;
+            total = total + i + j;
+        }
+        print(total);
+    ";
+    assert_prints(source, "19\n");
+}
+
+#[test]
+fn method_returns_leave_the_caller_untouched() {
+    let source = "
+        class Box {
+            init(value) { this.value = value; return; }
+            get() { { return this.value; } }
+        }
+        var value = \"caller\";
+        var b = Box(\"boxed\");
+        print(b.get());
+        print(value);
+        print(b.value);
+    ";
+    assert_prints(source, "boxed\ncaller\nboxed\n");
+}
+
+#[test]
+fn rethrowing_a_caught_error_keeps_the_original() {
+    let source = "
+        fn fail() { return \"a\" - 1; }
+        try {
+            try {
+                try { fail(); } catch (e) { throw e; }
+            } catch (e) {
+                print(e.kind + \" \" + str(e.line));
+                throw e;
+            }
+        } catch (e) {
+            print(e.kind + \" \" + str(e.line));
+            print(e.message);
+        }
+        try { try { throw 5; } catch (e) { throw e; } } catch (e) { print(e.value + e.line); }
+    ";
+    assert_prints(
+        source,
+        "InvalidBinaryOperation 2\nInvalidBinaryOperation 2\nInvalid Binary Operation at -. Operator can only be used on numbers\n19\n",
+    );
+}
@@ -0,0 +1,22 @@
+mod common;
+
+use common::run_with_input;
+
+#[test]
+fn input_reads_a_line_with_or_without_a_prompt() {
+    let source = "var name = input();\nvar age = input(\"age? \");\nprint(name + \" is \" + age);\n";
+    for command in ["interp", "run"] {
+        let run = run_with_input(command, source, "Ada\n36\n");
+        assert_eq!(run.code, Some(0), "{} failed:\n{}", command, run.stderr);
+        assert_eq!(run.stdout, "age? Ada is 36\n", "{}", command);
+    }
+}
+
+#[test]
+fn input_rejects_more_than_one_argument() {
+    for command in ["interp", "run"] {
+        let run = run_with_input(command, "input(\"a\", \"b\");\n", "");
+        assert_eq!(run.code, Some(70), "{}", command);
+        assert!(run.stderr.contains("Expected 1 arguments but got 2"), "{}:\n{}", command, run.stderr);
+    }
+}
@@ -0,0 +1,11 @@
+mod common;
+
+use common::assert_prints;
+
+#[test]
+fn integer_division_floors_and_slashes_start_comments() {
+    assert_prints(
+        "// a comment\nprint(7 ~/ 2); // trailing\nprint(-7 ~/ 2);\nprint(-7 % 2);\nprint(7 / 2);\nprint(~5);\n",
+        "3\n-4\n1\n3.5\n-6\n",
+    );
+}
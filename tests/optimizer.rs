@@ -0,0 +1,45 @@
+mod common;
+
+use common::run;
+
+// the tree `optimize` prints for `source`, less the blank line that ends it
+fn optimized(source: &str) -> String {
+    let run = run("optimize", source);
+    assert_eq!(run.code, Some(0), "optimize failed:\n{}", run.stderr);
+    run.stdout.strip_suffix('\n').unwrap_or(&run.stdout).to_string()
+}
+
+#[test]
+fn numeric_and_string_operations_are_folded() {
+    assert_eq!(optimized("print(1 + 2 * 3 - -4);\n"), "(print 11.0)\n");
+    assert_eq!(optimized("print(\"a\" + \"b\" + \"c\");\n"), "(print \"abc\")\n");
+    assert_eq!(optimized("print(!(1 < 2) == false);\n"), "(print true)\n");
+}
+
+#[test]
+fn constant_false_branches_and_loops_are_removed() {
+    let source = "if (false) { print(1); } else { print(2); }\nwhile (false) { print(3); }\nif (1 > 2) { print(4); }\nprint(5);\n";
+    assert_eq!(optimized(source), "(block (print 2.0))\n(print 5.0)\n");
+}
+
+#[test]
+fn branches_on_a_variable_condition_are_kept() {
+    let source = "var x = 1;\nif (x > 0) { print(1); } else { print(2); }\nwhile (x < 0) { print(3); }\n";
+    assert_eq!(
+        optimized(source),
+        "(var x 1.0)\n(if (> x 0.0)(block (print 1.0))(block (print 2.0)))\n(while (< x 0.0)(block (print 3.0)))\n",
+    );
+}
+
+#[test]
+fn operations_that_would_fail_are_left_for_runtime() {
+    let source = "print(1);\nprint(\"a\" - 1);\n";
+    assert_eq!(optimized(source), "(print 1.0)\n(print (- \"a\" 1.0))\n");
+    for command in ["interp", "run"] {
+        let run = run(command, source);
+        assert_eq!(run.code, Some(70), "{}", command);
+        assert_eq!(run.stdout, "1\n", "{}", command);
+        assert!(run.stderr.contains("Operator can only be used on numbers"), "{}:\n{}", command, run.stderr);
+        assert!(run.stderr.contains(":2:11\n"), "{}:\n{}", command, run.stderr);
+    }
+}
@@ -0,0 +1,47 @@
+mod common;
+
+use common::run;
+
+#[test]
+fn a_bad_class_member_is_reported_once() {
+    let source = "class A {\n fn f() { return 1; }\n}\nprint(\"unreached\");\n";
+    for command in ["interp", "run"] {
+        let run = run(command, source);
+        assert_eq!(run.code, Some(65), "{}", command);
+        assert_eq!(run.stdout, "", "{}", command);
+        assert_eq!(run.stderr.matches("ParserError").count(), 1, "{}:\n{}", command, run.stderr);
+        assert!(run.stderr.contains("Expect function name.\n --> "), "{}:\n{}", command, run.stderr);
+        assert!(run.stderr.contains(":2:2\n"), "{}:\n{}", command, run.stderr);
+    }
+}
+
+#[test]
+fn a_stray_closing_brace_is_reported_once() {
+    let source = "print(1);\n}\nprint(2);\n";
+    let run = run("interp", source);
+    assert_eq!(run.code, Some(65));
+    assert_eq!(run.stderr.matches("ParserError").count(), 1, "{}", run.stderr);
+    assert!(run.stderr.contains(":2:1\n"), "{}", run.stderr);
+}
+
+#[test]
+fn bad_escapes_stop_the_script_before_it_runs() {
+    let source = "print(1);\nprint(\"a\\q\");\n";
+    for command in ["interp", "run"] {
+        let run = run(command, source);
+        assert_eq!(run.code, Some(65), "{}", command);
+        assert_eq!(run.stdout, "", "{}", command);
+        assert!(run.stderr.contains("Unknown escape sequence: \\q"), "{}:\n{}", command, run.stderr);
+    }
+}
+
+#[test]
+fn an_unfinished_interpolated_expression_is_reported_at_its_brace() {
+    for (source, column) in [("print(\"a ${ 1 + } b\");\n", ":1:17\n"), ("print(\"${}\");\n", ":1:10\n")] {
+        let run = run("interp", source);
+        assert_eq!(run.code, Some(65), "{}", source);
+        assert_eq!(run.stderr.matches("ParserError").count(), 1, "{}", run.stderr);
+        assert!(run.stderr.contains("Expect expression inside '${...}'."), "{}", run.stderr);
+        assert!(run.stderr.contains(column), "{}", run.stderr);
+    }
+}
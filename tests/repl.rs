@@ -0,0 +1,69 @@
+use std::env;
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+// Feeds `input` to `salmon-rs repl` through a pipe and returns what it printed.
+// HOME points at the temp dir so the session's history file stays out of the way.
+fn repl(input: &str) -> String {
+    repl_at(&env::temp_dir(), input)
+}
+
+// `repl`, keeping its history file in `home`
+fn repl_at(home: &Path, input: &str) -> String {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_salmon-rs"))
+        .arg("repl")
+        .env("HOME", home)
+        .env("NO_COLOR", "1")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(input.as_bytes()).unwrap();
+    let output = child.wait_with_output().unwrap();
+    String::from_utf8_lossy(&output.stdout).into_owned()
+}
+
+#[test]
+fn brackets_and_quotes_inside_strings_do_not_continue_the_input() {
+    let stdout = repl("print(\"a\\\"{\");\nprint(\"(\");\nprint(2);\n");
+    assert!(stdout.contains(">> a\"{\n>> (\n>> 2\n"), "{}", stdout);
+    assert!(!stdout.contains(".. "), "{}", stdout);
+}
+
+#[test]
+fn open_strings_and_interpolations_continue_the_input() {
+    let stdout = repl("print(\"\"\"one\ntwo\"\"\");\nprint(\"${\n{\"k\": 1}[\"k\"] + 1\n} done\");\n");
+    assert!(stdout.contains(">> .. one\ntwo\n"), "{}", stdout);
+    assert!(stdout.contains(">> .. .. 2 done\n"), "{}", stdout);
+}
+
+#[test]
+fn unclosed_brackets_continue_the_input() {
+    let stdout = repl("fn f() {\nreturn 3;\n}\nprint(f());\n");
+    assert!(stdout.contains(">> .. .. >> 3\n"), "{}", stdout);
+}
+
+#[test]
+fn a_last_line_without_a_newline_still_runs() {
+    let stdout = repl("print(1);\nprint(2);");
+    assert!(stdout.contains(">> 1\n>> 2\n"), "{}", stdout);
+}
+
+#[test]
+fn the_history_file_is_trimmed_to_the_limit() {
+    let home = env::temp_dir().join(format!("salmon-repl-history-{}", std::process::id()));
+    fs::create_dir_all(&home).unwrap();
+    let old: Vec<String> = (0..1005).map(|i| format!("old {}", i)).collect();
+    fs::write(home.join(".salmon_history"), old.join("\n") + "\n").unwrap();
+
+    repl_at(&home, "print(3);\n");
+    let history = fs::read_to_string(home.join(".salmon_history")).unwrap();
+    fs::remove_dir_all(&home).unwrap();
+    let lines: Vec<&str> = history.lines().collect();
+    assert_eq!(lines.len(), 1000);
+    assert_eq!(lines[0], "old 6");
+    assert_eq!(lines[999], "print(3);");
+}
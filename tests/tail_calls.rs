@@ -0,0 +1,68 @@
+mod common;
+
+use common::{assert_prints, run};
+
+#[test]
+fn self_recursion_a_million_deep() {
+    let source = "
+        fn count(n, total) {
+            if (n == 0) return total;
+            return count(n - 1, total + 1);
+        }
+        print(count(1000000, 0));
+    ";
+    assert_prints(source, "1000000\n");
+}
+
+#[test]
+fn mutual_recursion() {
+    let source = "
+        fn even(n) { if (n == 0) return true; return odd(n - 1); }
+        fn odd(n) { if (n == 0) return false; return even(n - 1); }
+        print(even(20001));
+    ";
+    assert_prints(source, "false\n");
+}
+
+#[test]
+fn method_tail_calls() {
+    let source = "
+        class Countdown {
+            init(label) { this.label = label; }
+            from(n) { if (n == 0) return this.label; return this.from(n - 1); }
+        }
+        print(Countdown(\"liftoff\").from(20000));
+    ";
+    assert_prints(source, "liftoff\n");
+}
+
+#[test]
+fn calls_inside_try_still_unwind_through_it() {
+    let source = "
+        fn dive(n) {
+            try {
+                if (n == 0) throw \"bottom\";
+                return dive(n - 1);
+            } catch (e) {
+                return n;
+            }
+        }
+        print(dive(3));
+    ";
+    assert_prints(source, "0\n");
+}
+
+#[test]
+fn traceback_notes_elided_frames() {
+    let source = "
+        fn fall(n) { if (n == 0) return 1 / null; return fall(n - 1); }
+        fn start() { return fall(3) + 1; }
+        start();
+    ";
+    for command in ["interp", "run"] {
+        let run = run(command, source);
+        assert_eq!(run.code, Some(70), "{}", command);
+        assert!(run.stderr.contains("in start\n  ... 3 tail calls elided ...\n"), "{}:\n{}", command, run.stderr);
+        assert!(run.stderr.contains("in fall\n"), "{}:\n{}", command, run.stderr);
+    }
+}